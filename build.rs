@@ -0,0 +1,179 @@
+//! Parses all packfiles and encodes them into a compact blob that is
+//! embedded into the library.
+//!
+//! This means that no packfiles need to be read or parsed at runtime.
+//!
+//! The build script also generates the `no_std` device descriptors.
+
+#[cfg(feature = "serde")]
+extern crate serde;
+extern crate xmltree;
+extern crate zip;
+
+#[allow(dead_code)]
+#[path = "src/codec.rs"]
+mod codec;
+#[allow(dead_code)]
+#[path = "src/error.rs"]
+mod error;
+#[allow(dead_code)]
+#[path = "src/extra_info.rs"]
+mod extra_info;
+#[allow(dead_code)]
+#[path = "src/model.rs"]
+mod model;
+#[allow(dead_code)]
+#[path = "src/pack.rs"]
+mod pack;
+
+pub use error::{PackError, PackErrorKind};
+pub use model::*;
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::{env, fs, io};
+
+/// The extension on the pack files.
+const PACK_FILE_EXT: &str = "atdf";
+
+/// All pack collections inside the 'packs' folder, along with the cargo
+/// feature that includes them.
+const PACK_COLLECTIONS: &[(&str, &str)] = &[
+    ("atmega", "family-atmega"),
+    ("tiny", "family-tiny"),
+    ("xmegaa", "family-xmega"),
+    ("xmegab", "family-xmega"),
+    ("xmegac", "family-xmega"),
+    ("xmegad", "family-xmega"),
+    ("xmegae", "family-xmega"),
+    ("automotive", "family-automotive"),
+];
+
+/// The name of the generated encoded microcontrollers inside `OUT_DIR`.
+const OUTPUT_FILE_NAME: &str = "microcontrollers.bin";
+
+/// The name of the generated descriptors inside `OUT_DIR`.
+const DESCRIPTORS_FILE_NAME: &str = "descriptors.rs";
+
+fn main() {
+    let crate_root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let packs_dir = crate_root.join("packs");
+
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed={}", packs_dir.display());
+    for module in ["codec", "error", "extra_info", "model", "pack"].iter() {
+        println!("cargo:rerun-if-changed=src/{}.rs", module);
+    }
+
+    let mut paths = Vec::new();
+    for &(collection, feature) in PACK_COLLECTIONS.iter() {
+        if is_feature_enabled(feature) {
+            let mut packs =
+                find_packs(&packs_dir.join(collection)).expect("could not find packfiles");
+            packs.sort();
+            paths.extend(packs);
+        }
+    }
+
+    let mcus: Vec<Mcu> = paths
+        .iter()
+        .map(|path| pack::load(path))
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|e| panic!("failed to load microcontrollers: {}", e));
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    fs::write(out_dir.join(OUTPUT_FILE_NAME), codec::encode(&mcus))
+        .expect("could not write microcontrollers");
+    fs::write(out_dir.join(DESCRIPTORS_FILE_NAME), generate_descriptors(&mcus))
+        .expect("could not write descriptors");
+}
+
+/// Generates the contents of the `descriptors` module.
+fn generate_descriptors(mcus: &[Mcu]) -> String {
+    let mut code = String::new();
+
+    for mcu in mcus {
+        let memory = mcu.memory();
+        let size = |region: &Option<MemoryRegion>| region.as_ref().map_or(0, |r| r.size);
+        let signature: Vec<u32> = (0..3)
+            .map(|i| {
+                let name = format!("SIGNATURE{}", i);
+                mcu.property("SIGNATURES", &name).and_then(|p| p.as_int()).unwrap_or(0)
+            })
+            .collect();
+        let vector_count = mcu.device.interrupts.iter().map(|i| i.index + 1).max();
+
+        writeln!(code, "/// The {}.", mcu.device.name).unwrap();
+        writeln!(code, "pub const {}: Descriptor = Descriptor {{", const_name(mcu)).unwrap();
+        writeln!(code, "    name: {:?},", mcu.device.name).unwrap();
+        writeln!(code, "    core_architecture: {:?},", mcu.device.architecture.name()).unwrap();
+        writeln!(code, "    family: {:?},", mcu.device.family.name()).unwrap();
+        writeln!(code, "    flash_size: {:#x},", size(&memory.flash)).unwrap();
+        writeln!(code, "    flash_page_size: {:#x},", memory.spm_pagesize().unwrap_or(0)).unwrap();
+        writeln!(code, "    sram_start: {:#x},", memory.ramstart().unwrap_or(0)).unwrap();
+        writeln!(code, "    sram_size: {:#x},", size(&memory.sram)).unwrap();
+        writeln!(code, "    eeprom_size: {:#x},", size(&memory.eeprom)).unwrap();
+        writeln!(
+            code,
+            "    signature: [{:#04x}, {:#04x}, {:#04x}],",
+            signature[0], signature[1], signature[2]
+        )
+        .unwrap();
+        writeln!(code, "    vector_count: {},", vector_count.unwrap_or(0)).unwrap();
+        writeln!(code, "}};\n").unwrap();
+    }
+
+    writeln!(code, "/// All microcontrollers.").unwrap();
+    writeln!(code, "pub static DEVICES: &[Descriptor] = &[").unwrap();
+    for mcu in mcus {
+        writeln!(code, "    {},", const_name(mcu)).unwrap();
+    }
+    writeln!(code, "];\n").unwrap();
+
+    writeln!(code, "/// Gets the descriptor of a microcontroller at compile time.").unwrap();
+    writeln!(code, "///").unwrap();
+    writeln!(code, "/// The name must be a lowercase string literal, such as `\"atmega328p\"`.")
+        .unwrap();
+    writeln!(code, "#[macro_export]").unwrap();
+    writeln!(code, "macro_rules! device {{").unwrap();
+    for mcu in mcus {
+        writeln!(
+            code,
+            "    ({:?}) => {{ $crate::descriptors::{} }};",
+            mcu.device.name.to_lowercase(),
+            const_name(mcu)
+        )
+        .unwrap();
+    }
+    writeln!(
+        code,
+        "    ($name:expr) => {{ compile_error!(concat!(\"unknown microcontroller: \", $name)) }};"
+    )
+    .unwrap();
+    writeln!(code, "}}").unwrap();
+    code
+}
+
+/// Gets the name of the descriptor constant of a microcontroller.
+fn const_name(mcu: &Mcu) -> String {
+    mcu.device.name.to_uppercase()
+}
+
+/// Checks if a cargo feature of this crate is enabled.
+fn is_feature_enabled(feature: &str) -> bool {
+    let var = format!("CARGO_FEATURE_{}", feature.to_uppercase().replace('-', "_"));
+    env::var_os(var).is_some()
+}
+
+/// Finds all pack files in a directory.
+fn find_packs(in_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+
+    for entry in fs::read_dir(in_dir)? {
+        let entry = entry?;
+        if let Some(PACK_FILE_EXT) = entry.path().extension().and_then(|s| s.to_str()) {
+            paths.push(entry.path());
+        }
+    }
+    Ok(paths)
+}
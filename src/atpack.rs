@@ -0,0 +1,272 @@
+//! Reading device packs distributed by Microchip as `.atpack` archives.
+//!
+//! An `.atpack` file is a zip archive containing a `.pdsc` package
+//! description, along with one `atdf/<device>.atdf` packfile per device.
+
+use error::{PackError, PackErrorKind};
+use model::Mcu;
+use pack::{self, Node};
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use xmltree::Element;
+use zip::ZipArchive;
+
+/// The extension of package description files.
+const DESCRIPTION_FILE_EXT: &str = ".pdsc";
+
+/// A device pack archive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AtPack {
+    /// The path of the archive.
+    pub path: PathBuf,
+    /// The vendor of the pack, such as `Microchip`.
+    pub vendor: String,
+    /// The name of the pack, such as `ATmega_DFP`.
+    pub name: String,
+    /// The version of the latest release of the pack, such as `3.0.158`.
+    pub version: String,
+    /// The devices described by the pack.
+    pub devices: Vec<AtPackDevice>,
+}
+
+/// A device listed in a pack description.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AtPackDevice {
+    /// The name of the device, such as `ATmega328P`.
+    pub name: String,
+    /// The family the device is listed under, such as `ATmega`.
+    pub family: String,
+    /// The path of the packfile of the device inside the archive.
+    pub atdf_path: String,
+}
+
+impl AtPack {
+    /// Opens a pack archive and reads its package description.
+    pub fn open<P: Into<PathBuf>>(path: P) -> Result<Self, PackError> {
+        let path = path.into();
+        let mut archive = open_archive(&path)?;
+
+        let description_name = archive
+            .file_names()
+            .find(|name| name.ends_with(DESCRIPTION_FILE_EXT) && !name.contains('/'))
+            .map(|name| name.to_owned())
+            .ok_or_else(|| {
+                PackError::new(
+                    &path,
+                    PackErrorKind::MissingFile(format!("*{}", DESCRIPTION_FILE_EXT)),
+                )
+            })?;
+        let body = read_entry(&mut archive, &path, &description_name)?;
+
+        parse_description(&body, path.join(&description_name))
+            .map(|description| AtPack { path, ..description })
+    }
+
+    /// Gets a listed device by name, ignoring case.
+    pub fn device(&self, name: &str) -> Option<&AtPackDevice> {
+        self.devices.iter().find(|d| d.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Parses the packfiles of all listed devices.
+    pub fn microcontrollers(&self) -> Result<Vec<Mcu>, PackError> {
+        let mut archive = open_archive(&self.path)?;
+
+        self.devices.iter().map(|device| self.read_device(&mut archive, device)).collect()
+    }
+
+    /// Parses the packfile of a single listed device.
+    pub fn microcontroller(&self, name: &str) -> Result<Mcu, PackError> {
+        let device = self.device(name).ok_or_else(|| {
+            PackError::new(&self.path, PackErrorKind::UnknownMcu(name.to_owned()))
+        })?;
+        self.read_device(&mut open_archive(&self.path)?, device)
+    }
+
+    fn read_device(
+        &self, archive: &mut ZipArchive<File>, device: &AtPackDevice,
+    ) -> Result<Mcu, PackError> {
+        let body = read_entry(archive, &self.path, &device.atdf_path)?;
+        pack::parse(&body, &self.path.join(&device.atdf_path))
+    }
+}
+
+fn open_archive(path: &Path) -> Result<ZipArchive<File>, PackError> {
+    let file = File::open(path).map_err(|e| PackError::new(path, PackErrorKind::Io(e)))?;
+    ZipArchive::new(file).map_err(|e| PackError::new(path, PackErrorKind::Zip(e)))
+}
+
+fn read_entry(
+    archive: &mut ZipArchive<File>, path: &Path, name: &str,
+) -> Result<String, PackError> {
+    let mut entry =
+        archive.by_name(name).map_err(|e| PackError::new(path, PackErrorKind::Zip(e)))?;
+    let mut body = String::new();
+    entry
+        .read_to_string(&mut body)
+        .map_err(|e| PackError::new(path.join(name), PackErrorKind::Io(e)))?;
+    Ok(body)
+}
+
+/// Parses a package description.
+///
+/// The path of the returned pack is left empty.
+fn parse_description(body: &str, path: PathBuf) -> Result<AtPack, PackError> {
+    let root = Element::parse(body.as_bytes())
+        .map_err(|e| PackError::new(&path, PackErrorKind::Xml(e)))?;
+
+    read_description(&Node::root(&root)).map_err(|e| PackError { path, ..e })
+}
+
+fn read_description(package: &Node) -> Result<AtPack, PackError> {
+    let text = |name: &str| -> Result<String, PackError> {
+        Ok(package.child(name)?.text().trim().to_owned())
+    };
+    let version = match package.child("releases")?.children().next() {
+        Some(release) => release.attr("version")?.clone(),
+        None => String::new(),
+    };
+
+    let mut devices = Vec::new();
+    for family in package.child("devices")?.children() {
+        read_devices(&family, family.attr("Dfamily")?, &mut devices)?;
+    }
+
+    Ok(AtPack {
+        path: PathBuf::new(),
+        vendor: text("vendor")?,
+        name: text("name")?,
+        version,
+        devices,
+    })
+}
+
+/// Reads all devices in a `<family>` or `<subFamily>` element.
+fn read_devices(
+    parent: &Node, family: &str, devices: &mut Vec<AtPackDevice>,
+) -> Result<(), PackError> {
+    for child in parent.children() {
+        match child.name() {
+            "subFamily" => read_devices(&child, family, devices)?,
+            "device" => {
+                let name = child.attr("Dname")?.clone();
+                let atdf_path =
+                    find_atdf_path(&child).unwrap_or_else(|| format!("atdf/{}.atdf", name));
+                devices.push(AtPackDevice { name, family: family.to_owned(), atdf_path });
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Finds the `<at:atdf name="...">` element inside the environment
+/// extensions of a device.
+fn find_atdf_path(element: &Node) -> Option<String> {
+    element.children().find_map(|child| {
+        if child.name() == "atdf" {
+            child.opt_attr("name").cloned()
+        } else {
+            find_atdf_path(&child)
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs;
+    use std::io::Write;
+
+    use tempfile::TempDir;
+    use zip::write::{FileOptions, ZipWriter};
+
+    const DESCRIPTION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <package schemaVersion="1.4" xmlns:atmel="http://www.atmel.com/schemas/pack-device-atmel-extension">
+          <vendor>Microchip</vendor>
+          <name>ATtiny_DFP</name>
+          <releases>
+            <release version="2.0.368">Newest release</release>
+            <release version="1.10.348">Older release</release>
+          </releases>
+          <devices>
+            <family Dfamily="ATtiny" Dvendor="Microchip:3">
+              <device Dname="ATtiny85">
+                <environment name="atmel">
+                  <atmel:extension>
+                    <atmel:atdf name="atdf/ATtiny85.atdf"/>
+                  </atmel:extension>
+                </environment>
+              </device>
+              <subFamily DsubFamily="tinyAVR 1-series">
+                <device Dname="ATtiny816"/>
+              </subFamily>
+            </family>
+          </devices>
+        </package>"#;
+
+    /// Writes a pack archive into a temporary directory, which is removed
+    /// when it is dropped.
+    fn write_atpack(description_name: &str) -> (TempDir, PathBuf) {
+        let packs = Path::new(env!("CARGO_MANIFEST_DIR")).join("packs").join("tiny");
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("Microchip.ATtiny_DFP.atpack");
+
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        zip.start_file(description_name, FileOptions::default()).unwrap();
+        zip.write_all(DESCRIPTION.as_bytes()).unwrap();
+        for device in ["ATtiny85", "ATtiny816"].iter() {
+            let body = fs::read(packs.join(format!("{}.atdf", device))).unwrap();
+            zip.start_file(format!("atdf/{}.atdf", device), FileOptions::default()).unwrap();
+            zip.write_all(&body).unwrap();
+        }
+        zip.finish().unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn atpack_description_is_read() {
+        let (_dir, path) = write_atpack("Microchip.ATtiny_DFP.pdsc");
+        let pack = AtPack::open(path).unwrap();
+
+        assert_eq!("Microchip", pack.vendor);
+        assert_eq!("ATtiny_DFP", pack.name);
+        assert_eq!("2.0.368", pack.version);
+        assert_eq!(2, pack.devices.len());
+        assert_eq!("ATtiny", pack.devices[1].family);
+        assert_eq!("atdf/ATtiny816.atdf", pack.device("attiny816").unwrap().atdf_path);
+    }
+
+    #[test]
+    fn atpack_devices_are_parsed() {
+        let (_dir, path) = write_atpack("Microchip.ATtiny_DFP.pdsc");
+        let pack = AtPack::open(path).unwrap();
+
+        let mcus = pack.microcontrollers().unwrap();
+        assert_eq!(
+            vec!["ATtiny85", "ATtiny816"],
+            mcus.iter().map(|mcu| mcu.device.name.as_str()).collect::<Vec<_>>()
+        );
+
+        let loaded = ::PackLoader::new().atpack(pack.path.clone()).load().unwrap();
+        assert_eq!(mcus, loaded);
+
+        let error = pack.microcontroller("atmega328p").unwrap_err();
+        assert!(matches!(error.kind, PackErrorKind::UnknownMcu(_)));
+    }
+
+    #[test]
+    fn atpack_without_description_is_an_error() {
+        let (_dir, path) = write_atpack("docs/Microchip.ATtiny_DFP.pdsc");
+        let error = AtPack::open(&path).unwrap_err();
+
+        assert_eq!(path, error.path);
+        match error.kind {
+            PackErrorKind::MissingFile(ref name) => assert_eq!("*.pdsc", name),
+            ref kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+}
@@ -0,0 +1,401 @@
+//! A command-line tool for browsing the device data of this crate.
+//!
+//! Run `avr-mcu help` for usage.
+
+extern crate avr_mcu;
+#[macro_use]
+extern crate json;
+
+use avr_mcu::{descriptors, Mcu};
+use json::JsonValue;
+
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::{env, process};
+
+const USAGE: &str = "\
+usage: avr-mcu [--json] <command> [arguments]
+
+commands:
+    list                        List all microcontrollers
+    show <mcu>                  Show an overview of a microcontroller
+    registers <mcu> [module]    List the registers, optionally of one module only
+    interrupts <mcu>            List the interrupt vectors
+    pins <mcu> [package]        List the pins, optionally of one package only
+    search <pattern>            Find microcontrollers, peripherals, registers and
+                                interrupts whose name contains a pattern
+
+options:
+    --json                      Print JSON instead of human-readable text";
+
+/// How to print results.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Format {
+    Human,
+    Json,
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let format = match args.iter().position(|arg| arg == "--json") {
+        Some(index) => {
+            args.remove(index);
+            Format::Json
+        }
+        None => Format::Human,
+    };
+
+    match run(&args, format) {
+        Ok(output) => print!("{}", output),
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            process::exit(1);
+        }
+    }
+}
+
+/// Runs a command, returning its output.
+fn run(args: &[String], format: Format) -> Result<String, String> {
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+
+    let output = match args.as_slice() {
+        ["list"] => list(format),
+        ["show", mcu] => show(find_mcu(mcu)?, format),
+        ["registers", mcu] => registers(find_mcu(mcu)?, None, format)?,
+        ["registers", mcu, module] => registers(find_mcu(mcu)?, Some(module), format)?,
+        ["interrupts", mcu] => interrupts(find_mcu(mcu)?, format),
+        ["pins", mcu] => pins(find_mcu(mcu)?, None, format)?,
+        ["pins", mcu, package] => pins(find_mcu(mcu)?, Some(package), format)?,
+        ["search", pattern] => search(pattern, format),
+        ["help"] | ["--help"] | ["-h"] => format!("{}\n", USAGE),
+        [] => return Err("no command given".to_owned()),
+        _ => return Err(format!("invalid command '{}'", args.join(" "))),
+    };
+    Ok(output)
+}
+
+/// Formats a list of results as JSON.
+fn to_json(values: Vec<JsonValue>) -> String {
+    json::stringify_pretty(values, 2) + "\n"
+}
+
+fn find_mcu(name: &str) -> Result<&'static Mcu, String> {
+    avr_mcu::try_microcontroller(name).map_err(|_| format!("unknown microcontroller '{}'", name))
+}
+
+fn list(format: Format) -> String {
+    let mcus = avr_mcu::microcontrollers();
+
+    match format {
+        Format::Json => to_json(
+            mcus.iter()
+                .map(|mcu| {
+                    object! {
+                        "name" => mcu.device.name.as_str(),
+                        "family" => mcu.device.family.name(),
+                        "architecture" => mcu.architecture.name(),
+                    }
+                })
+                .collect::<Vec<_>>(),
+        ),
+        Format::Human => {
+            let mut output = String::new();
+            for mcu in mcus {
+                writeln!(
+                    output,
+                    "{:<16} {:<10} {}",
+                    mcu.device.name,
+                    mcu.architecture.name(),
+                    mcu.device.family.name()
+                )
+                .unwrap();
+            }
+            output
+        }
+    }
+}
+
+fn show(mcu: &Mcu, format: Format) -> String {
+    if format == Format::Json {
+        return mcu.to_json() + "\n";
+    }
+
+    let mut output = String::new();
+    writeln!(output, "{}", mcu.device.name).unwrap();
+    writeln!(output, "  family:       {}", mcu.device.family.name()).unwrap();
+    writeln!(output, "  core:         {}", mcu.device.architecture.name()).unwrap();
+    writeln!(output, "  architecture: {}", mcu.architecture.name()).unwrap();
+
+    if let Some(descriptor) = descriptors::device(&mcu.device.name) {
+        writeln!(output, "  flash:        {} bytes", descriptor.flash_size).unwrap();
+        writeln!(output, "  sram:         {} bytes", descriptor.sram_size).unwrap();
+        writeln!(output, "  eeprom:       {} bytes", descriptor.eeprom_size).unwrap();
+        let signature = descriptor.signature;
+        writeln!(
+            output,
+            "  signature:    {:02x} {:02x} {:02x}",
+            signature[0], signature[1], signature[2]
+        )
+        .unwrap();
+    }
+
+    let interfaces: Vec<_> = mcu.device.interfaces.iter().map(|i| i.name()).collect();
+    writeln!(output, "  interfaces:   {}", interfaces.join(", ")).unwrap();
+
+    writeln!(output, "\nvariants:").unwrap();
+    for variant in mcu.variants.iter() {
+        writeln!(
+            output,
+            "  {:<20} {:<10} {}-{} V, {} MHz, {} to {} °C",
+            variant.name,
+            variant.package,
+            variant.voltage_min,
+            variant.voltage_max,
+            variant.speed_max_hz / 1_000_000,
+            variant.temperature_min,
+            variant.temperature_max
+        )
+        .unwrap();
+    }
+
+    writeln!(output, "\nperipherals:").unwrap();
+    for peripheral in mcu.device.peripherals.iter() {
+        let instances: Vec<_> = peripheral.instances.iter().map(|i| i.name.as_str()).collect();
+        writeln!(output, "  {:<12} {}", peripheral.name, instances.join(", ")).unwrap();
+    }
+    output
+}
+
+#[allow(clippy::unnecessary_map_or)]
+fn registers(mcu: &Mcu, module: Option<&str>, format: Format) -> Result<String, String> {
+    let peripherals: Vec<_> = mcu
+        .device
+        .peripherals
+        .iter()
+        .filter(|p| module.map_or(true, |module| p.name.eq_ignore_ascii_case(module)))
+        .collect();
+    if let (Some(module), true) = (module, peripherals.is_empty()) {
+        return Err(format!("{} has no module '{}'", mcu.device.name, module));
+    }
+
+    let mut rows = Vec::new();
+    for peripheral in peripherals {
+        for instance in peripheral.instances.iter() {
+            rows.extend(mcu.instance_registers(instance).into_iter().map(|r| (instance, r)));
+        }
+    }
+
+    Ok(match format {
+        Format::Json => to_json(
+            rows.iter()
+                .map(|(instance, register)| {
+                    object! {
+                        "instance" => instance.name.as_str(),
+                        "name" => register.name.as_str(),
+                        "address_space" => register.address_space,
+                        "address" => register.address,
+                        "size" => register.register.size,
+                        "caption" => register.register.caption.as_str(),
+                    }
+                })
+                .collect::<Vec<_>>(),
+        ),
+        Format::Human => {
+            let mut output = String::new();
+            for (instance, register) in rows {
+                writeln!(
+                    output,
+                    "{:#06x} {:<8} {:<24} {}",
+                    register.address, instance.name, register.name, register.register.caption
+                )
+                .unwrap();
+            }
+            output
+        }
+    })
+}
+
+fn interrupts(mcu: &Mcu, format: Format) -> String {
+    let interrupts = &mcu.device.interrupts;
+
+    match format {
+        Format::Json => to_json(
+            interrupts
+                .iter()
+                .map(|interrupt| {
+                    object! {
+                        "index" => interrupt.index,
+                        "name" => interrupt.name.as_str(),
+                        "caption" => interrupt.caption.as_str(),
+                    }
+                })
+                .collect::<Vec<_>>(),
+        ),
+        Format::Human => {
+            let mut output = String::new();
+            for interrupt in interrupts {
+                writeln!(
+                    output,
+                    "{:>3} {:<24} {}",
+                    interrupt.index, interrupt.name, interrupt.caption
+                )
+                .unwrap();
+            }
+            output
+        }
+    }
+}
+
+fn pins(mcu: &Mcu, package: Option<&str>, format: Format) -> Result<String, String> {
+    let pinouts: Vec<_> = match package {
+        Some(package) => {
+            // Accept both pinout names and package names of variants.
+            let pinout =
+                mcu.pinouts.iter().find(|p| p.name.eq_ignore_ascii_case(package)).or_else(|| {
+                    mcu.variants
+                        .iter()
+                        .filter(|v| v.package.eq_ignore_ascii_case(package))
+                        .find_map(|v| v.pinout(mcu))
+                });
+            match pinout {
+                Some(pinout) => vec![pinout],
+                None => {
+                    return Err(format!("{} has no package '{}'", mcu.device.name, package));
+                }
+            }
+        }
+        None => mcu.pinouts.iter().collect(),
+    };
+
+    Ok(match format {
+        Format::Json => to_json(
+            pinouts
+                .iter()
+                .map(|pinout| {
+                    let pins: Vec<JsonValue> = pinout
+                        .pins
+                        .iter()
+                        .map(|pin| {
+                            object! {
+                                "position" => pin.position.as_str(),
+                                "pad" => pin.pad.as_str(),
+                            }
+                        })
+                        .collect();
+                    object! {
+                        "pinout" => pinout.name.as_str(),
+                        "pins" => pins,
+                    }
+                })
+                .collect::<Vec<_>>(),
+        ),
+        Format::Human => {
+            let mut output = String::new();
+            for pinout in pinouts {
+                writeln!(output, "{}:", pinout.name).unwrap();
+                for pin in pinout.pins.iter() {
+                    writeln!(output, "  {:>4} {}", pin.position, pin.pad).unwrap();
+                }
+            }
+            output
+        }
+    })
+}
+
+fn search(pattern: &str, format: Format) -> String {
+    let pattern = pattern.to_lowercase();
+    let matches = |name: &str| name.to_lowercase().contains(&pattern);
+
+    let mut results: Vec<(&str, &str, &str)> = Vec::new();
+    for mcu in avr_mcu::microcontrollers() {
+        let mcu_name = mcu.device.name.as_str();
+        if matches(mcu_name) {
+            results.push((mcu_name, "mcu", mcu_name));
+        }
+        for peripheral in mcu.device.peripherals.iter() {
+            for instance in peripheral.instances.iter().filter(|i| matches(&i.name)) {
+                results.push((mcu_name, "peripheral", &instance.name));
+            }
+        }
+        for register in mcu.registers().filter(|r| matches(&r.name)) {
+            results.push((mcu_name, "register", &register.name));
+        }
+        for interrupt in mcu.device.interrupts.iter().filter(|i| matches(&i.name)) {
+            results.push((mcu_name, "interrupt", &interrupt.name));
+        }
+    }
+    // The same register can be found in several register groups.
+    let mut seen = HashSet::new();
+    results.retain(|&result| seen.insert(result));
+
+    match format {
+        Format::Json => to_json(
+            results
+                .iter()
+                .map(|&(mcu, kind, name)| {
+                    object! {
+                        "mcu" => mcu,
+                        "kind" => kind,
+                        "name" => name,
+                    }
+                })
+                .collect::<Vec<_>>(),
+        ),
+        Format::Human => {
+            let mut output = String::new();
+            for (mcu, kind, name) in results {
+                writeln!(output, "{:<16} {:<10} {}", mcu, kind, name).unwrap();
+            }
+            output
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run_command(command: &str, format: Format) -> Result<String, String> {
+        let args: Vec<String> = command.split_whitespace().map(|arg| arg.to_owned()).collect();
+        run(&args, format)
+    }
+
+    #[test]
+    #[cfg(feature = "family-atmega")]
+    fn registers_are_listed_with_addresses() {
+        let output = run_command("registers atmega328p usart", Format::Human).unwrap();
+        assert!(output.lines().any(|line| line.starts_with("0x00c6 USART0   UDR0")));
+
+        let output = run_command("registers atmega328p usart", Format::Json).unwrap();
+        let registers = json::parse(&output).unwrap();
+        assert!(registers.members().any(|r| r["name"] == "UDR0" && r["address"] == 0xc6));
+    }
+
+    #[test]
+    #[cfg(feature = "family-tiny")]
+    fn pins_can_be_filtered_by_package() {
+        let output = run_command("pins attiny85 PDIP8", Format::Json).unwrap();
+        let pinouts = json::parse(&output).unwrap();
+        assert_eq!(1, pinouts.len());
+        assert_eq!(8, pinouts[0]["pins"].len());
+    }
+
+    #[test]
+    #[cfg(feature = "family-tiny")]
+    fn search_results_are_unique() {
+        let output = run_command("search ctrla", Format::Json).unwrap();
+        let results: Vec<_> = json::parse(&output).unwrap().members().map(|r| r.dump()).collect();
+        assert!(!results.is_empty());
+
+        let unique: HashSet<_> = results.iter().collect();
+        assert_eq!(unique.len(), results.len());
+    }
+
+    #[test]
+    fn invalid_commands_are_errors() {
+        assert!(run_command("", Format::Human).is_err());
+        assert!(run_command("show atmega9000", Format::Human).is_err());
+        assert!(run_command("registers atmega328p nothing", Format::Human).is_err());
+        assert!(run_command("frobnicate", Format::Human).is_err());
+    }
+}
@@ -0,0 +1,399 @@
+//! A compact binary encoding of the device model.
+//!
+//! The build script parses every packfile and encodes the resulting
+//! microcontrollers with this module. The library embeds the encoded data
+//! and decodes it on first use, so that no packfiles need to be read at
+//! runtime.
+//!
+//! Integers are stored as LEB128 varints. Strings are deduplicated into a
+//! table at the start of the data and referenced by index, as most names
+//! and captions are shared between many devices.
+
+use model::*;
+
+use std::collections::{BTreeMap, HashMap};
+
+/// A type that can be written to the binary encoding.
+pub trait Encode {
+    fn encode(&self, writer: &mut Writer);
+}
+
+/// A type that can be read from the binary encoding.
+pub trait Decode: Sized {
+    fn decode(reader: &mut Reader) -> Self;
+}
+
+/// Encodes a list of microcontrollers.
+#[allow(dead_code)]
+pub fn encode(mcus: &[Mcu]) -> Vec<u8> {
+    let mut writer = Writer::default();
+    mcus.len().encode(&mut writer);
+    for mcu in mcus {
+        mcu.encode(&mut writer);
+    }
+    writer.finish()
+}
+
+/// Decodes a list of microcontrollers.
+///
+/// # Panics
+///
+/// Panics if the data was not produced by `encode`.
+#[allow(dead_code)]
+pub fn decode(data: &[u8]) -> Vec<Mcu> {
+    let mut reader = Reader::new(data);
+    Vec::decode(&mut reader)
+}
+
+#[derive(Default)]
+pub struct Writer {
+    strings: Vec<String>,
+    string_indices: HashMap<String, u64>,
+    data: Vec<u8>,
+}
+
+impl Writer {
+    fn write_varint(data: &mut Vec<u8>, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                data.push(byte);
+                return;
+            }
+            data.push(byte | 0x80);
+        }
+    }
+
+    fn varint(&mut self, value: u64) {
+        Self::write_varint(&mut self.data, value);
+    }
+
+    fn string(&mut self, value: &str) {
+        let index = match self.string_indices.get(value) {
+            Some(&index) => index,
+            None => {
+                let index = self.strings.len() as u64;
+                self.strings.push(value.to_owned());
+                self.string_indices.insert(value.to_owned(), index);
+                index
+            }
+        };
+        self.varint(index);
+    }
+
+    /// Gets the string table followed by the encoded values.
+    fn finish(self) -> Vec<u8> {
+        let mut result = Vec::new();
+        Self::write_varint(&mut result, self.strings.len() as u64);
+        for string in self.strings.iter() {
+            Self::write_varint(&mut result, string.len() as u64);
+            result.extend_from_slice(string.as_bytes());
+        }
+        result.extend(self.data);
+        result
+    }
+}
+
+pub struct Reader<'a> {
+    strings: Vec<&'a str>,
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        let mut reader = Reader { strings: Vec::new(), data };
+
+        let count = reader.varint();
+        for _ in 0..count {
+            let len = reader.varint() as usize;
+            let (string, rest) = reader.data.split_at(len);
+            reader.strings.push(::std::str::from_utf8(string).expect("invalid string"));
+            reader.data = rest;
+        }
+        reader
+    }
+
+    fn byte(&mut self) -> u8 {
+        let (&byte, rest) = self.data.split_first().expect("unexpected end of data");
+        self.data = rest;
+        byte
+    }
+
+    fn varint(&mut self) -> u64 {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte();
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return value;
+            }
+            shift += 7;
+        }
+    }
+
+    fn string(&mut self) -> &'a str {
+        let index = self.varint() as usize;
+        self.strings[index]
+    }
+}
+
+macro_rules! impl_codec_for_int {
+    ($($ty:ty),*) => {
+        $(
+            impl Encode for $ty {
+                fn encode(&self, writer: &mut Writer) {
+                    writer.varint(*self as u64);
+                }
+            }
+
+            impl Decode for $ty {
+                fn decode(reader: &mut Reader) -> Self {
+                    reader.varint() as $ty
+                }
+            }
+        )*
+    };
+}
+
+impl_codec_for_int!(u8, u32, u64, usize);
+
+impl Encode for i32 {
+    fn encode(&self, writer: &mut Writer) {
+        writer.varint(((self << 1) ^ (self >> 31)) as u32 as u64);
+    }
+}
+
+impl Decode for i32 {
+    fn decode(reader: &mut Reader) -> Self {
+        let value = reader.varint() as u32;
+        ((value >> 1) as i32) ^ -((value & 1) as i32)
+    }
+}
+
+impl Encode for f32 {
+    fn encode(&self, writer: &mut Writer) {
+        self.to_bits().encode(writer);
+    }
+}
+
+impl Decode for f32 {
+    fn decode(reader: &mut Reader) -> Self {
+        f32::from_bits(u32::decode(reader))
+    }
+}
+
+impl Encode for bool {
+    fn encode(&self, writer: &mut Writer) {
+        writer.varint(*self as u64);
+    }
+}
+
+impl Decode for bool {
+    fn decode(reader: &mut Reader) -> Self {
+        reader.varint() != 0
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, writer: &mut Writer) {
+        writer.string(self);
+    }
+}
+
+impl Decode for String {
+    fn decode(reader: &mut Reader) -> Self {
+        reader.string().to_owned()
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, writer: &mut Writer) {
+        match self {
+            Some(value) => {
+                writer.varint(1);
+                value.encode(writer);
+            }
+            None => writer.varint(0),
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode(reader: &mut Reader) -> Self {
+        match reader.varint() {
+            0 => None,
+            _ => Some(T::decode(reader)),
+        }
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, writer: &mut Writer) {
+        self.len().encode(writer);
+        for item in self.iter() {
+            item.encode(writer);
+        }
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(reader: &mut Reader) -> Self {
+        let len = usize::decode(reader);
+        (0..len).map(|_| T::decode(reader)).collect()
+    }
+}
+
+impl<K: Encode + Ord, V: Encode> Encode for BTreeMap<K, V> {
+    fn encode(&self, writer: &mut Writer) {
+        self.len().encode(writer);
+        for (key, value) in self.iter() {
+            key.encode(writer);
+            value.encode(writer);
+        }
+    }
+}
+
+impl<K: Decode + Ord, V: Decode> Decode for BTreeMap<K, V> {
+    fn decode(reader: &mut Reader) -> Self {
+        let len = usize::decode(reader);
+        (0..len).map(|_| (K::decode(reader), V::decode(reader))).collect()
+    }
+}
+
+/// Implements the encoding for a struct by encoding its fields in order.
+macro_rules! impl_codec_for_struct {
+    ($($ty:ident { $($field:ident),* })*) => {
+        $(
+            impl Encode for $ty {
+                fn encode(&self, writer: &mut Writer) {
+                    $( self.$field.encode(writer); )*
+                }
+            }
+
+            impl Decode for $ty {
+                fn decode(reader: &mut Reader) -> Self {
+                    $ty { $( $field: Decode::decode(reader), )* }
+                }
+            }
+        )*
+    };
+}
+
+impl_codec_for_struct! {
+    Mcu { device, variants, modules, pinouts, architecture, c_preprocessor_name }
+    Device {
+        name, architecture, family, address_spaces, peripherals, interrupts, property_groups,
+        interfaces, parameters
+    }
+    Variant {
+        name, pinout, package, temperature_min, temperature_max, voltage_min, voltage_max,
+        speed_max_hz
+    }
+    Pinout { name, caption, pins }
+    Pin { position, pad }
+    AddressSpace { id, name, start_address, size, segments }
+    MemorySegment { name, start_address, size, ty, readable, writable, executable, page_size }
+    Peripheral { name, instances }
+    Interrupt { name, caption, index }
+    PropertyGroup { name, properties }
+    Property { name, value }
+    Module { name, register_groups, value_groups, parameters }
+    Instance { name, caption, register_groups, signals, parameters }
+    RegisterGroup { name, caption, size, registers, register_groups, union_tag }
+    RegisterGroupRef { name, name_in_module, caption, offset, union_tag_value, address_space }
+    UnionTag { path }
+    ValueGroup { name, caption, values }
+    Value { name, caption, value }
+    Register {
+        name, caption, offset, size, mask, initial_value, rw, ocd_rw, bitfields, modes
+    }
+    RegisterMode { name, bitfields }
+    Bitfield { name, caption, mask, size, access, values }
+    Signal { pad, group, index, function }
+}
+
+/// Implements the encoding for an enum that can be converted from and to
+/// its name in the packfiles.
+macro_rules! impl_codec_for_named_enum {
+    ($($ty:ident),*) => {
+        $(
+            impl Encode for $ty {
+                fn encode(&self, writer: &mut Writer) {
+                    writer.string(self.name());
+                }
+            }
+
+            impl Decode for $ty {
+                fn decode(reader: &mut Reader) -> Self {
+                    $ty::from_name(reader.string())
+                }
+            }
+        )*
+    };
+}
+
+impl_codec_for_named_enum!(ProgrammingInterface, CoreArchitecture, Family);
+
+/// Implements the encoding for a fieldless enum by encoding the index of
+/// the variant.
+macro_rules! impl_codec_for_enum {
+    ($($ty:ident { $($variant:ident),* })*) => {
+        $(
+            impl Encode for $ty {
+                fn encode(&self, writer: &mut Writer) {
+                    let variants = [$($ty::$variant),*];
+                    let index = variants.iter().position(|v| v == self).unwrap();
+                    index.encode(writer);
+                }
+            }
+
+            impl Decode for $ty {
+                fn decode(reader: &mut Reader) -> Self {
+                    let variants = [$($ty::$variant),*];
+                    variants[usize::decode(reader)]
+                }
+            }
+        )*
+    };
+}
+
+impl_codec_for_enum! {
+    ReadWrite { ReadAndWrite, ReadOnly, WriteOnly, NoAccess }
+    Architecture {
+        Unknown, Avr0, Avr1, Avr2, Avr25, Avr3, Avr31, Avr35, Avr4, Avr5, Avr51, Avr6, Xmega2,
+        Xmega3, Xmega4, Xmega5, Xmega6, Xmega7, Tiny
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn round_trip<T: Encode + Decode>(value: &T) -> T {
+        let mut writer = Writer::default();
+        value.encode(&mut writer);
+        T::decode(&mut Reader::new(&writer.finish()))
+    }
+
+    #[test]
+    fn integers_round_trip() {
+        let values = vec![0, 1, -1, 127, 128, -300, i32::MAX, i32::MIN];
+        assert_eq!(values, round_trip(&values));
+        assert_eq!(u64::MAX, round_trip(&u64::MAX));
+        assert_eq!(-3.3f32, round_trip(&-3.3f32));
+    }
+
+    #[test]
+    fn microcontrollers_round_trip() {
+        let packs = ::std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("packs");
+        let mcus: Vec<Mcu> =
+            ["atmega/ATmega328P.atdf", "tiny/ATtiny816.atdf", "xmegaa/ATxmega128A1.atdf"]
+                .iter()
+                .map(|path| ::pack::load(&packs.join(path)).unwrap())
+                .collect();
+
+        assert_eq!(mcus, decode(&encode(&mcus)));
+    }
+}
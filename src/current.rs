@@ -9,7 +9,7 @@ use Mcu;
 /// Returns `None` if the target architecture is not AVR.
 ///
 /// When targeting AVR, this function will always return `Some(mcu)`.
-pub fn mcu() -> Option<Mcu> {
+pub fn mcu() -> Option<&'static Mcu> {
     mcu_name().map(|mcu_name| load::microcontroller(&mcu_name))
 }
 
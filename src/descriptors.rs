@@ -0,0 +1,77 @@
+//! Compact descriptions of all microcontrollers that are usable without `std`.
+//!
+//! These are generated by the build script from the packfiles, and consist
+//! only of `&'static` data. They are available even without the `std` feature,
+//! so firmware can query information about the microcontroller it runs on.
+//!
+//! # Examples
+//!
+//! ```nodoc
+//! const MCU: avr_mcu::descriptors::Descriptor = avr_mcu::device!("attiny85");
+//! const HAS_EEPROM: bool = MCU.eeprom_size > 0;
+//! ```
+
+/// A compact description of a microcontroller.
+///
+/// Sizes are in bytes. Memories that do not exist have a size of zero.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Descriptor {
+    /// The name of the microcontroller, such as `ATmega328P`.
+    pub name: &'static str,
+    /// The name of the CPU architecture in the packfile, such as `AVR8`.
+    pub core_architecture: &'static str,
+    /// The name of the family in the packfile, such as `megaAVR`.
+    pub family: &'static str,
+    /// The size of the flash memory.
+    pub flash_size: u32,
+    /// The size of a flash page.
+    pub flash_page_size: u32,
+    /// The address of the internal SRAM in the data address space.
+    pub sram_start: u32,
+    /// The size of the internal SRAM.
+    pub sram_size: u32,
+    /// The size of the EEPROM.
+    pub eeprom_size: u32,
+    /// The device signature bytes.
+    pub signature: [u8; 3],
+    /// The number of entries in the interrupt vector table, including reset.
+    pub vector_count: u32,
+}
+
+include!(concat!(env!("OUT_DIR"), "/descriptors.rs"));
+
+/// Gets the descriptor of a microcontroller by name, ignoring case.
+///
+/// Use the `device!` macro to get a descriptor at compile time.
+pub fn device(name: &str) -> Option<&'static Descriptor> {
+    DEVICES.iter().find(|device| device.name.eq_ignore_ascii_case(name))
+}
+
+#[cfg(all(test, any(feature = "family-atmega", feature = "family-tiny")))]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "family-atmega")]
+    fn atmega328p_is_described() {
+        const ATMEGA328P: Descriptor = device!("atmega328p");
+
+        assert_eq!(Some(&ATMEGA328P), device("ATmega328P"));
+        assert_eq!(0x8000, ATMEGA328P.flash_size);
+        assert_eq!(0x80, ATMEGA328P.flash_page_size);
+        assert_eq!((0x100, 0x800), (ATMEGA328P.sram_start, ATMEGA328P.sram_size));
+        assert_eq!(0x400, ATMEGA328P.eeprom_size);
+        assert_eq!([0x1e, 0x95, 0x0f], ATMEGA328P.signature);
+        assert_eq!(26, ATMEGA328P.vector_count);
+    }
+
+    #[test]
+    #[cfg(feature = "family-tiny")]
+    fn avr8x_memories_are_described() {
+        let attiny816 = device!("attiny816");
+        assert_eq!(Some(&attiny816), device("attiny816"));
+        assert_eq!((0x3e00, 0x200), (attiny816.sram_start, attiny816.sram_size));
+        assert_eq!(0x80, attiny816.eeprom_size);
+        assert_eq!(0x40, attiny816.flash_page_size);
+    }
+}
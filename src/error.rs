@@ -0,0 +1,98 @@
+//! Errors that can occur while loading packfiles.
+
+use std::error::Error;
+use std::path::PathBuf;
+use std::{fmt, io};
+
+use xmltree::ParseError;
+use zip::result::ZipError;
+
+/// An error encountered while loading a packfile.
+#[derive(Debug)]
+pub struct PackError {
+    /// The path of the packfile.
+    pub path: PathBuf,
+    /// The path of the offending element inside the packfile.
+    ///
+    /// This looks like `devices/device[ATmega328P]/peripherals/module[USART]`.
+    /// It is empty if the error does not concern a specific element.
+    pub element_path: String,
+    /// What went wrong.
+    pub kind: PackErrorKind,
+}
+
+/// The different kinds of errors that can occur while loading a packfile.
+#[derive(Debug)]
+pub enum PackErrorKind {
+    /// The packfile could not be read.
+    Io(io::Error),
+    /// The packfile is not valid XML.
+    Xml(ParseError),
+    /// The pack archive is not a valid zip file, or lacks a file.
+    Zip(ZipError),
+    /// A required file is missing from a pack archive.
+    MissingFile(String),
+    /// A required child element is missing.
+    MissingElement(String),
+    /// A required attribute is missing.
+    MissingAttribute(String),
+    /// An attribute has a value that could not be interpreted.
+    MalformedAttribute {
+        /// The name of the attribute.
+        attribute: String,
+        /// The value of the attribute.
+        value: String,
+    },
+    /// An element contains a child element that is not expected there.
+    UnexpectedElement(String),
+    /// There is no packfile for a microcontroller.
+    UnknownMcu(String),
+}
+
+impl PackError {
+    /// Creates a new error that concerns a whole packfile.
+    pub fn new<P: Into<PathBuf>>(path: P, kind: PackErrorKind) -> Self {
+        PackError { path: path.into(), element_path: String::new(), kind }
+    }
+}
+
+impl fmt::Display for PackError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.path.display())?;
+        if !self.element_path.is_empty() {
+            write!(fmt, ": {}", self.element_path)?;
+        }
+        write!(fmt, ": {}", self.kind)
+    }
+}
+
+impl fmt::Display for PackErrorKind {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PackErrorKind::Io(e) => write!(fmt, "could not read packfile: {}", e),
+            PackErrorKind::Xml(e) => write!(fmt, "invalid XML: {}", e),
+            PackErrorKind::Zip(e) => write!(fmt, "invalid pack archive: {}", e),
+            PackErrorKind::MissingFile(name) => write!(fmt, "missing file '{}'", name),
+            PackErrorKind::MissingElement(name) => write!(fmt, "missing element '{}'", name),
+            PackErrorKind::MissingAttribute(name) => write!(fmt, "missing attribute '{}'", name),
+            PackErrorKind::MalformedAttribute { attribute, value } => {
+                write!(fmt, "malformed attribute '{}' with value '{}'", attribute, value)
+            }
+            PackErrorKind::UnexpectedElement(name) => write!(fmt, "unexpected element '{}'", name),
+            PackErrorKind::UnknownMcu(name) => {
+                write!(fmt, "no microcontroller with the name '{}' found", name)
+            }
+        }
+    }
+}
+
+impl Error for PackError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.kind {
+            PackErrorKind::Io(e) => Some(e),
+            PackErrorKind::Xml(e) => Some(e),
+            PackErrorKind::Zip(e) => Some(e),
+            _ => None,
+        }
+    }
+}
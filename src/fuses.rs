@@ -0,0 +1,543 @@
+//! Decoding and encoding of fuse bytes.
+//!
+//! Fuses are described by the `FUSE` module of a microcontroller. Each fuse
+//! byte is a register, and each setting is a bitfield of that register, with
+//! an optional value group naming its possible values.
+
+use model::{Bitfield, CoreArchitecture, Mcu, Module, Register, Value};
+
+use std::error::Error;
+use std::fmt;
+
+/// The fuse layout of a microcontroller.
+///
+/// # Examples
+///
+/// ```nodoc
+/// use avr_mcu::FuseValue;
+///
+/// let fuses = avr_mcu::microcontroller("atmega328p").fuses().unwrap();
+/// let bytes = fuses
+///     .encode(&[("BODLEVEL", FuseValue::Named("2V7")), ("CKDIV8", FuseValue::Programmed(false))])
+///     .unwrap();
+///
+/// for setting in fuses.decode(&bytes).unwrap() {
+///     println!("{}: {}", setting.name(), setting.caption());
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fuses<'a> {
+    layout: ConfigLayout<'a>,
+    active_low: bool,
+}
+
+/// A setting decoded from fuse bytes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FuseSetting<'a> {
+    /// The fuse byte that holds the setting.
+    pub register: &'a Register,
+    /// The bitfield of the setting, or `None` if the setting is a whole
+    /// fuse byte without bitfields, such as `BOOTEND` on AVR8X parts.
+    pub bitfield: Option<&'a Bitfield>,
+    /// The value of the setting, shifted to start at bit 0.
+    pub value: u32,
+    /// The entry of the value group that matches the value, if any.
+    pub named_value: Option<&'a Value>,
+    /// Whether a single-bit fuse without a value group is programmed.
+    pub programmed: Option<bool>,
+}
+
+/// A value to encode into a fuse setting.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FuseValue<'s> {
+    /// Programs (`true`) or unprograms (`false`) a single-bit fuse.
+    ///
+    /// This takes the polarity of the fuses into account.
+    Programmed(bool),
+    /// The name of an entry of the setting's value group, such as `2V7`.
+    Named(&'s str),
+    /// A raw value, shifted to start at bit 0.
+    Raw(u32),
+}
+
+/// An error encountered while decoding or encoding fuse or lock bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FuseError {
+    /// The number of bytes does not match the layout.
+    WrongLength {
+        /// The number of bytes of the layout.
+        expected: usize,
+        /// The number of bytes given.
+        actual: usize,
+    },
+    /// There is no setting with the name.
+    UnknownSetting(String),
+    /// The value group of the setting has no entry with the name.
+    UnknownValue {
+        /// The name of the setting.
+        setting: String,
+        /// The name of the value.
+        value: String,
+    },
+    /// A raw value does not fit into the setting.
+    ValueOutOfRange {
+        /// The name of the setting.
+        setting: String,
+        /// The raw value.
+        value: u32,
+    },
+    /// A setting that is wider than one bit was programmed or unprogrammed.
+    NotASingleBit(String),
+    /// The packfile gives no default value (`initval`) for the fuse byte
+    /// with the name.
+    MissingDefault(String),
+}
+
+/// The registers of a module instance that is programmed as a block of
+/// bytes, such as the fuses or the lock bits.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ConfigLayout<'a> {
+    pub(crate) module: &'a Module,
+    /// The registers, ordered by offset.
+    pub(crate) registers: Vec<&'a Register>,
+    /// The number of bytes.
+    pub(crate) len: usize,
+}
+
+/// A register and bitfield, along with its value shifted to start at bit 0.
+pub(crate) type Field<'a> = (&'a Register, Option<&'a Bitfield>, u32);
+
+impl Mcu {
+    /// Gets the fuse layout, if the microcontroller has fuses.
+    pub fn fuses(&self) -> Option<Fuses<'_>> {
+        Some(Fuses {
+            layout: ConfigLayout::new(self, "FUSE")?,
+            active_low: self.device.architecture != CoreArchitecture::Avr8X,
+        })
+    }
+}
+
+impl<'a> Fuses<'a> {
+    /// Gets the fuse bytes, ordered by offset.
+    pub fn registers(&self) -> &[&'a Register] {
+        &self.layout.registers
+    }
+
+    /// Gets the number of fuse bytes.
+    pub fn len(&self) -> usize {
+        self.layout.len
+    }
+
+    /// Checks if there are no fuse bytes.
+    pub fn is_empty(&self) -> bool {
+        self.layout.len == 0
+    }
+
+    /// Checks if a fuse bit is programmed when it is 0.
+    ///
+    /// This is the case for all parts except AVR8X parts, whose fuses are
+    /// plain configuration values.
+    pub fn is_active_low(&self) -> bool {
+        self.active_low
+    }
+
+    /// Gets the default fuse bytes.
+    ///
+    /// These are the `initval`s of the packfile. Fails if a fuse byte has
+    /// none, as is the case for many AVR8X parts. Reserved bytes that are not
+    /// part of any fuse byte are left unprogrammed.
+    pub fn defaults(&self) -> Result<Vec<u8>, FuseError> {
+        if let Some(register) = self.layout.registers.iter().find(|r| r.initial_value.is_none()) {
+            return Err(FuseError::MissingDefault(register.name.clone()));
+        }
+        let unprogrammed = if self.active_low { 0xFF } else { 0x00 };
+        Ok(self.layout.defaults(unprogrammed))
+    }
+
+    /// Decodes fuse bytes into settings, ordered by fuse byte.
+    pub fn decode(&self, bytes: &[u8]) -> Result<Vec<FuseSetting<'a>>, FuseError> {
+        let mut settings = Vec::new();
+        for (register, bitfield, value) in self.layout.fields(bytes)? {
+            let named_value = bitfield.and_then(|b| self.layout.named_value(b, value));
+            let programmed = bitfield
+                .filter(|b| b.mask.count_ones() == 1 && b.values.is_none())
+                .map(|_| (value == 0) == self.active_low);
+
+            settings.push(FuseSetting { register, bitfield, value, named_value, programmed });
+        }
+        Ok(settings)
+    }
+
+    /// Encodes settings into fuse bytes, starting from the defaults.
+    ///
+    /// A setting is named by its bitfield, such as `BODLEVEL`, or by its fuse
+    /// byte if it has no bitfields. Names can be qualified by their fuse byte,
+    /// such as `HIGH.BOOTSZ`, and are not case sensitive.
+    ///
+    /// Fails if the packfile gives no defaults, see `encode_from`.
+    pub fn encode(&self, settings: &[(&str, FuseValue)]) -> Result<Vec<u8>, FuseError> {
+        self.encode_from(&self.defaults()?, settings)
+    }
+
+    /// Encodes settings into fuse bytes, starting from the given bytes.
+    ///
+    /// This is typically used with the fuse bytes read from a part.
+    pub fn encode_from(
+        &self, base: &[u8], settings: &[(&str, FuseValue)],
+    ) -> Result<Vec<u8>, FuseError> {
+        if base.len() != self.layout.len {
+            return Err(FuseError::WrongLength { expected: self.layout.len, actual: base.len() });
+        }
+
+        let mut bytes = base.to_vec();
+        for &(name, value) in settings {
+            self.set(&mut bytes, name, value)?;
+        }
+        Ok(bytes)
+    }
+
+    /// Changes a single setting in fuse bytes.
+    pub fn set(&self, bytes: &mut [u8], name: &str, value: FuseValue) -> Result<(), FuseError> {
+        let (register, bitfield) = self.layout.find(name)?;
+        let raw = match value {
+            FuseValue::Programmed(programmed) => match bitfield {
+                Some(bitfield) if bitfield.mask.count_ones() == 1 => {
+                    (programmed != self.active_low) as u32
+                }
+                _ => return Err(FuseError::NotASingleBit(name.to_owned())),
+            },
+            FuseValue::Named(value) => self.layout.value_by_name(name, bitfield, value)?,
+            FuseValue::Raw(value) => value,
+        };
+        self.layout.write(bytes, name, register, bitfield, raw)
+    }
+}
+
+impl<'a> FuseSetting<'a> {
+    /// Gets the name of the setting.
+    pub fn name(&self) -> &'a str {
+        match self.bitfield {
+            Some(bitfield) => &bitfield.name,
+            None => &self.register.name,
+        }
+    }
+
+    /// Gets a description of the value of the setting.
+    ///
+    /// This is the caption of the value group entry, or else `programmed`,
+    /// `unprogrammed` or the raw value in hex.
+    pub fn caption(&self) -> String {
+        match (self.named_value, self.programmed) {
+            (Some(value), _) => value.caption.clone(),
+            (None, Some(true)) => "programmed".to_owned(),
+            (None, Some(false)) => "unprogrammed".to_owned(),
+            (None, None) => format!("{:#x}", self.value),
+        }
+    }
+}
+
+impl<'a> ConfigLayout<'a> {
+    /// Finds the layout of the registers of a peripheral instance.
+    ///
+    /// Older XMEGA packfiles make the `FUSE` and `LOCKBIT` instances part of
+    /// the `NVM` peripheral, so the instance is looked up by name.
+    pub(crate) fn new(mcu: &'a Mcu, instance_name: &str) -> Option<Self> {
+        let peripheral =
+            mcu.device.peripherals.iter().find(|p| p.instance(instance_name).is_some())?;
+        let instance = peripheral.instance(instance_name)?;
+        let module = mcu.module(&peripheral.name)?;
+        let group = module.resolve_register_group(instance.register_groups.first()?)?;
+
+        let mut registers: Vec<&Register> = group.registers.iter().collect();
+        registers.sort_by_key(|r| r.offset);
+        let end = registers.iter().map(|r| r.offset + r.size).max().unwrap_or(0);
+        let len = group.size.unwrap_or(0).max(end) as usize;
+
+        Some(ConfigLayout { module, registers, len })
+    }
+
+    /// Gets the default bytes, using a value for registers without an `initval`.
+    pub(crate) fn defaults(&self, fallback: u8) -> Vec<u8> {
+        let mut bytes = vec![fallback; self.len];
+        for register in self.registers.iter() {
+            if let Some(value) = register.initial_value {
+                write_register(&mut bytes, register, value);
+            }
+        }
+        bytes
+    }
+
+    /// Splits bytes into the values of all bitfields, and of all registers
+    /// without bitfields.
+    pub(crate) fn fields(&self, bytes: &[u8]) -> Result<Vec<Field<'a>>, FuseError> {
+        if bytes.len() != self.len {
+            return Err(FuseError::WrongLength { expected: self.len, actual: bytes.len() });
+        }
+
+        let mut fields = Vec::new();
+        for &register in self.registers.iter() {
+            let value = read_register(bytes, register);
+            if register.bitfields.is_empty() {
+                fields.push((register, None, value));
+            }
+            for bitfield in register.bitfields.iter() {
+                fields.push((register, Some(bitfield), field_value(bitfield.mask, value)));
+            }
+        }
+        Ok(fields)
+    }
+
+    /// Finds the register and bitfield of a setting.
+    #[allow(clippy::unnecessary_map_or)]
+    pub(crate) fn find(
+        &self, name: &str,
+    ) -> Result<(&'a Register, Option<&'a Bitfield>), FuseError> {
+        let (register_name, field_name) = match name.find('.') {
+            Some(index) => (Some(&name[..index]), &name[index + 1..]),
+            None => (None, name),
+        };
+        let registers = self
+            .registers
+            .iter()
+            .filter(|r| register_name.map_or(true, |n| r.name.eq_ignore_ascii_case(n)));
+
+        for &register in registers {
+            if register.bitfields.is_empty() && register.name.eq_ignore_ascii_case(field_name) {
+                return Ok((register, None));
+            }
+            if let Some(bitfield) =
+                register.bitfields.iter().find(|b| b.name.eq_ignore_ascii_case(field_name))
+            {
+                return Ok((register, Some(bitfield)));
+            }
+        }
+        Err(FuseError::UnknownSetting(name.to_owned()))
+    }
+
+    /// Finds the value group entry of a bitfield value.
+    pub(crate) fn named_value(&self, bitfield: &Bitfield, value: u32) -> Option<&'a Value> {
+        let group = self.module.value_group(bitfield.values.as_ref()?)?;
+        group.values.iter().find(|v| v.value == value)
+    }
+
+    /// Looks up the value of a value group entry by name.
+    pub(crate) fn value_by_name(
+        &self, setting: &str, bitfield: Option<&Bitfield>, value: &str,
+    ) -> Result<u32, FuseError> {
+        bitfield
+            .and_then(|b| self.module.value_group(b.values.as_ref()?))
+            .and_then(|group| group.values.iter().find(|v| v.name.eq_ignore_ascii_case(value)))
+            .map(|v| v.value)
+            .ok_or_else(|| FuseError::UnknownValue {
+                setting: setting.to_owned(),
+                value: value.to_owned(),
+            })
+    }
+
+    /// Writes the value of a setting into bytes.
+    pub(crate) fn write(
+        &self, bytes: &mut [u8], setting: &str, register: &Register, bitfield: Option<&Bitfield>,
+        value: u32,
+    ) -> Result<(), FuseError> {
+        if bytes.len() != self.len {
+            return Err(FuseError::WrongLength { expected: self.len, actual: bytes.len() });
+        }
+
+        let mask = bitfield.map_or_else(|| register_mask(register), |b| b.mask);
+        let shift = mask.trailing_zeros();
+        if value > mask >> shift {
+            return Err(FuseError::ValueOutOfRange { setting: setting.to_owned(), value });
+        }
+
+        let old = read_register(bytes, register);
+        write_register(bytes, register, (old & !mask) | (value << shift));
+        Ok(())
+    }
+}
+
+/// Extracts the value of a bitfield from a register value.
+fn field_value(mask: u32, value: u32) -> u32 {
+    (value & mask) >> mask.trailing_zeros()
+}
+
+/// Gets the mask that covers all bits of a register.
+fn register_mask(register: &Register) -> u32 {
+    match register.size {
+        0..=3 => (1 << (register.size * 8)) - 1,
+        _ => u32::MAX,
+    }
+}
+
+/// Reads a little-endian register value from bytes.
+fn read_register(bytes: &[u8], register: &Register) -> u32 {
+    (0..register.size)
+        .rev()
+        .fold(0, |value, i| (value << 8) | u32::from(bytes[(register.offset + i) as usize]))
+}
+
+/// Writes a little-endian register value into bytes.
+fn write_register(bytes: &mut [u8], register: &Register, value: u32) {
+    for i in 0..register.size {
+        bytes[(register.offset + i) as usize] = (value >> (i * 8)) as u8;
+    }
+}
+
+impl fmt::Display for FuseError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FuseError::WrongLength { expected, actual } => {
+                write!(fmt, "expected {} bytes, got {}", expected, actual)
+            }
+            FuseError::UnknownSetting(name) => write!(fmt, "no setting with the name '{}'", name),
+            FuseError::UnknownValue { setting, value } => {
+                write!(fmt, "setting '{}' has no value with the name '{}'", setting, value)
+            }
+            FuseError::ValueOutOfRange { setting, value } => {
+                write!(fmt, "value {:#x} does not fit into setting '{}'", value, setting)
+            }
+            FuseError::NotASingleBit(name) => {
+                write!(fmt, "setting '{}' is not a single-bit fuse", name)
+            }
+            FuseError::MissingDefault(name) => {
+                write!(fmt, "fuse byte '{}' has no default value", name)
+            }
+        }
+    }
+}
+
+impl Error for FuseError {}
+
+#[cfg(all(
+    test,
+    any(feature = "family-atmega", feature = "family-tiny", feature = "family-xmega")
+))]
+mod test {
+    use super::*;
+
+    fn fuses(name: &str) -> Fuses<'static> {
+        ::microcontroller(name).fuses().unwrap()
+    }
+
+    fn setting<'a>(settings: &[FuseSetting<'a>], name: &str) -> FuseSetting<'a> {
+        *settings.iter().find(|s| s.name() == name).unwrap()
+    }
+
+    #[test]
+    #[cfg(feature = "family-atmega")]
+    fn atmega328p_fuses_are_decoded() {
+        let fuses = fuses("atmega328p");
+        assert!(fuses.is_active_low());
+        assert_eq!(Ok(vec![0x62, 0xD9, 0xFF]), fuses.defaults());
+
+        let settings = fuses.decode(&[0xFF, 0xDA, 0xFD]).unwrap();
+        let sut_cksel = setting(&settings, "SUT_CKSEL");
+        assert_eq!(
+            Some("EXTXOSC_8MHZ_XX_16KCK_14CK_65MS"),
+            sut_cksel.named_value.map(|v| &v.name[..])
+        );
+        assert_eq!("Brown-out detection at VCC=2.7 V", setting(&settings, "BODLEVEL").caption());
+        assert_eq!(
+            Some("1024W_3C00"),
+            setting(&settings, "BOOTSZ").named_value.map(|v| &v.name[..])
+        );
+        assert_eq!(Some(true), setting(&settings, "SPIEN").programmed);
+        assert_eq!(Some(true), setting(&settings, "BOOTRST").programmed);
+        assert_eq!("unprogrammed", setting(&settings, "CKDIV8").caption());
+
+        assert_eq!(
+            Err(FuseError::WrongLength { expected: 3, actual: 2 }),
+            fuses.decode(&[0xFF, 0xFF])
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "family-atmega")]
+    fn atmega328p_fuses_are_encoded() {
+        let fuses = fuses("atmega328p");
+        let bytes = fuses
+            .encode(&[
+                ("SUT_CKSEL", FuseValue::Named("extxosc_8mhz_xx_16kck_14ck_65ms")),
+                ("CKDIV8", FuseValue::Programmed(false)),
+                ("HIGH.BOOTSZ", FuseValue::Named("512W_3E00")),
+                ("BODLEVEL", FuseValue::Raw(0x5)),
+            ])
+            .unwrap();
+        assert_eq!(vec![0xFF, 0xDD, 0xFD], bytes);
+
+        let mut bytes = bytes;
+        fuses.set(&mut bytes, "BOOTRST", FuseValue::Programmed(true)).unwrap();
+        assert_eq!(0xDC, bytes[1]);
+
+        let error = |name, value| fuses.encode(&[(name, value)]).unwrap_err();
+        assert_eq!(FuseError::UnknownSetting("NOPE".to_owned()), error("NOPE", FuseValue::Raw(0)));
+        assert_eq!(
+            FuseError::ValueOutOfRange { setting: "BOOTSZ".to_owned(), value: 4 },
+            error("BOOTSZ", FuseValue::Raw(4))
+        );
+        assert_eq!(
+            FuseError::NotASingleBit("BODLEVEL".to_owned()),
+            error("BODLEVEL", FuseValue::Programmed(true))
+        );
+        assert!(matches!(
+            error("BODLEVEL", FuseValue::Named("9V9")),
+            FuseError::UnknownValue { .. }
+        ));
+    }
+
+    #[test]
+    fn all_fuse_defaults_can_be_decoded() {
+        for mcu in ::microcontrollers() {
+            if let Some(fuses) = mcu.fuses() {
+                if let Ok(defaults) = fuses.defaults() {
+                    assert!(fuses.decode(&defaults).is_ok(), "{}", mcu.device.name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "family-xmega")]
+    fn xmega_fuses_are_found_in_the_nvm_peripheral() {
+        // Older XMEGA packfiles make FUSE an instance of the NVM peripheral.
+        let fuses = fuses("atxmega128a1");
+        assert_eq!(6, fuses.len());
+
+        let settings = fuses.decode(&[0xFF; 6]).unwrap();
+        assert_eq!(Some(false), setting(&settings, "JTAGEN").programmed);
+    }
+
+    #[test]
+    #[cfg(feature = "family-tiny")]
+    fn fuses_without_defaults_are_encoded_from_given_bytes() {
+        let fuses = fuses("attiny816");
+        assert!(!fuses.is_active_low());
+        assert_eq!(9, fuses.len());
+
+        let missing = FuseError::MissingDefault("WDTCFG".to_owned());
+        assert_eq!(Err(missing.clone()), fuses.defaults());
+        assert_eq!(Err(missing), fuses.encode(&[("EESAVE", FuseValue::Programmed(true))]));
+
+        let bytes = fuses
+            .encode_from(
+                &[0x00, 0x00, 0x02, 0x00, 0x00, 0xC4, 0x06, 0x00, 0x00],
+                &[
+                    ("EESAVE", FuseValue::Programmed(true)),
+                    ("RSTPINCFG", FuseValue::Named("UPDI")),
+                    ("BOOTEND", FuseValue::Raw(0x02)),
+                ],
+            )
+            .unwrap();
+        assert_eq!(0xC5, bytes[5]);
+        assert_eq!(0x02, bytes[8]);
+
+        let settings = fuses.decode(&bytes).unwrap();
+        assert_eq!(Some(true), setting(&settings, "EESAVE").programmed);
+        assert_eq!("UPDI mode", setting(&settings, "RSTPINCFG").caption());
+        assert_eq!(None, setting(&settings, "BOOTEND").bitfield);
+
+        assert_eq!(
+            Err(FuseError::WrongLength { expected: 9, actual: 2 }),
+            fuses.encode_from(&[0x00, 0x00], &[])
+        );
+    }
+}
@@ -0,0 +1,430 @@
+//! Conversion of the device model from and to JSON.
+//!
+//! Objects use the field names of the model. Enums with a packfile name,
+//! such as `CoreArchitecture`, are written as that name. `Architecture` is
+//! written as its avr-gcc name and `ReadWrite` in snake case, such as
+//! `read_only`. Missing optional values are written as `null`.
+
+use json::{self, JsonValue};
+use model::*;
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::Path;
+use std::{fmt, fs, io};
+
+/// The extension on the JSON files written by `write_json_files`.
+const JSON_FILE_EXT: &str = "json";
+
+/// An error encountered while reading a device description from JSON.
+#[derive(Debug)]
+pub struct JsonError {
+    /// The path of the offending value, such as `device.peripherals[3].name`.
+    ///
+    /// It is empty if the error concerns the whole document.
+    pub path: String,
+    /// What went wrong.
+    pub kind: JsonErrorKind,
+}
+
+/// The different kinds of errors that can occur while reading JSON.
+#[derive(Debug)]
+pub enum JsonErrorKind {
+    /// The document is not valid JSON.
+    Syntax(json::Error),
+    /// A value is missing or has the wrong type.
+    Expected(&'static str),
+}
+
+impl Mcu {
+    /// Converts the mcu to a JSON document.
+    pub fn to_json(&self) -> String {
+        json::stringify(self.to_json_value())
+    }
+
+    /// Reads a mcu from a JSON document written by `to_json`.
+    pub fn from_json(document: &str) -> Result<Mcu, JsonError> {
+        let value = json::parse(document)
+            .map_err(|e| JsonError { path: String::new(), kind: JsonErrorKind::Syntax(e) })?;
+        Mcu::from_json_value(&value, "")
+    }
+}
+
+/// Writes the JSON description of each mcu to `<name>.json` in a directory.
+///
+/// The directory is created if it does not exist. The file names are the
+/// lowercase mcu names, such as `atmega328p.json`.
+pub fn write_json_files<P: AsRef<Path>>(mcus: &[Mcu], dir: P) -> io::Result<()> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+
+    for mcu in mcus {
+        let file_name = format!("{}.{}", mcu.device.name.to_lowercase(), JSON_FILE_EXT);
+        fs::write(dir.join(file_name), json::stringify_pretty(mcu.to_json_value(), 2))?;
+    }
+    Ok(())
+}
+
+trait ToJson {
+    fn to_json_value(&self) -> JsonValue;
+}
+
+trait FromJson: Sized {
+    /// Reads a value, using the path to describe errors.
+    fn from_json_value(value: &JsonValue, path: &str) -> Result<Self, JsonError>;
+}
+
+fn expected<T>(path: &str, what: &'static str) -> Result<T, JsonError> {
+    Err(JsonError { path: path.to_owned(), kind: JsonErrorKind::Expected(what) })
+}
+
+/// Gets the path of a field inside an object.
+fn field_path(path: &str, field: &str) -> String {
+    if path.is_empty() {
+        field.to_owned()
+    } else {
+        format!("{}.{}", path, field)
+    }
+}
+
+macro_rules! impl_json_for_number {
+    ($($ty:ty => $as_ty:ident),*) => {
+        $(
+            impl ToJson for $ty {
+                fn to_json_value(&self) -> JsonValue {
+                    (*self).into()
+                }
+            }
+
+            impl FromJson for $ty {
+                fn from_json_value(value: &JsonValue, path: &str) -> Result<Self, JsonError> {
+                    match value.$as_ty() {
+                        Some(number) => Ok(number),
+                        None => expected(path, stringify!($ty)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_json_for_number!(u8 => as_u8, u32 => as_u32, u64 => as_u64, i32 => as_i32);
+
+impl ToJson for f32 {
+    fn to_json_value(&self) -> JsonValue {
+        // Go through the shortest decimal representation, so that `1.8`
+        // is not written as `1.7999999523162842`.
+        self.to_string().parse::<f64>().unwrap_or_default().into()
+    }
+}
+
+impl FromJson for f32 {
+    fn from_json_value(value: &JsonValue, path: &str) -> Result<Self, JsonError> {
+        match value.as_f32() {
+            Some(number) => Ok(number),
+            None => expected(path, "number"),
+        }
+    }
+}
+
+impl ToJson for bool {
+    fn to_json_value(&self) -> JsonValue {
+        (*self).into()
+    }
+}
+
+impl FromJson for bool {
+    fn from_json_value(value: &JsonValue, path: &str) -> Result<Self, JsonError> {
+        match value.as_bool() {
+            Some(b) => Ok(b),
+            None => expected(path, "boolean"),
+        }
+    }
+}
+
+impl ToJson for String {
+    fn to_json_value(&self) -> JsonValue {
+        self.as_str().into()
+    }
+}
+
+impl FromJson for String {
+    fn from_json_value(value: &JsonValue, path: &str) -> Result<Self, JsonError> {
+        match value.as_str() {
+            Some(s) => Ok(s.to_owned()),
+            None => expected(path, "string"),
+        }
+    }
+}
+
+impl<T: ToJson> ToJson for Option<T> {
+    fn to_json_value(&self) -> JsonValue {
+        match self {
+            Some(value) => value.to_json_value(),
+            None => JsonValue::Null,
+        }
+    }
+}
+
+impl<T: FromJson> FromJson for Option<T> {
+    fn from_json_value(value: &JsonValue, path: &str) -> Result<Self, JsonError> {
+        if value.is_null() {
+            Ok(None)
+        } else {
+            T::from_json_value(value, path).map(Some)
+        }
+    }
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn to_json_value(&self) -> JsonValue {
+        JsonValue::Array(self.iter().map(ToJson::to_json_value).collect())
+    }
+}
+
+impl<T: FromJson> FromJson for Vec<T> {
+    fn from_json_value(value: &JsonValue, path: &str) -> Result<Self, JsonError> {
+        if !value.is_array() {
+            return expected(path, "array");
+        }
+        value
+            .members()
+            .enumerate()
+            .map(|(i, member)| T::from_json_value(member, &format!("{}[{}]", path, i)))
+            .collect()
+    }
+}
+
+impl ToJson for BTreeMap<String, String> {
+    fn to_json_value(&self) -> JsonValue {
+        let mut object = JsonValue::new_object();
+        for (key, value) in self.iter() {
+            object[key.as_str()] = value.to_json_value();
+        }
+        object
+    }
+}
+
+impl FromJson for BTreeMap<String, String> {
+    fn from_json_value(value: &JsonValue, path: &str) -> Result<Self, JsonError> {
+        if !value.is_object() {
+            return expected(path, "object");
+        }
+        value
+            .entries()
+            .map(|(key, value)| {
+                Ok((key.to_owned(), String::from_json_value(value, &field_path(path, key))?))
+            })
+            .collect()
+    }
+}
+
+/// Implements the conversion for a struct by converting each field into an
+/// object member of the same name.
+macro_rules! impl_json_for_struct {
+    ($($ty:ident { $($field:ident),* })*) => {
+        $(
+            impl ToJson for $ty {
+                fn to_json_value(&self) -> JsonValue {
+                    let mut object = JsonValue::new_object();
+                    $( object[stringify!($field)] = self.$field.to_json_value(); )*
+                    object
+                }
+            }
+
+            impl FromJson for $ty {
+                fn from_json_value(value: &JsonValue, path: &str) -> Result<Self, JsonError> {
+                    if !value.is_object() {
+                        return expected(path, "object");
+                    }
+                    Ok($ty {
+                        $(
+                            $field: FromJson::from_json_value(
+                                &value[stringify!($field)],
+                                &field_path(path, stringify!($field)),
+                            )?,
+                        )*
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl_json_for_struct! {
+    Mcu { device, variants, modules, pinouts, architecture, c_preprocessor_name }
+    Device {
+        name, architecture, family, address_spaces, peripherals, interrupts, property_groups,
+        interfaces, parameters
+    }
+    Variant {
+        name, pinout, package, temperature_min, temperature_max, voltage_min, voltage_max,
+        speed_max_hz
+    }
+    Pinout { name, caption, pins }
+    Pin { position, pad }
+    AddressSpace { id, name, start_address, size, segments }
+    MemorySegment { name, start_address, size, ty, readable, writable, executable, page_size }
+    Peripheral { name, instances }
+    Interrupt { name, caption, index }
+    PropertyGroup { name, properties }
+    Property { name, value }
+    Module { name, register_groups, value_groups, parameters }
+    Instance { name, caption, register_groups, signals, parameters }
+    RegisterGroup { name, caption, size, registers, register_groups, union_tag }
+    RegisterGroupRef { name, name_in_module, caption, offset, union_tag_value, address_space }
+    UnionTag { path }
+    ValueGroup { name, caption, values }
+    Value { name, caption, value }
+    Register {
+        name, caption, offset, size, mask, initial_value, rw, ocd_rw, bitfields, modes
+    }
+    RegisterMode { name, bitfields }
+    Bitfield { name, caption, mask, size, access, values }
+    Signal { pad, group, index, function }
+}
+
+/// Implements the conversion for an enum that can be converted from and to
+/// its name in the packfiles.
+macro_rules! impl_json_for_named_enum {
+    ($($ty:ident),*) => {
+        $(
+            impl ToJson for $ty {
+                fn to_json_value(&self) -> JsonValue {
+                    self.name().into()
+                }
+            }
+
+            impl FromJson for $ty {
+                fn from_json_value(value: &JsonValue, path: &str) -> Result<Self, JsonError> {
+                    match value.as_str() {
+                        Some(name) => Ok($ty::from_name(name)),
+                        None => expected(path, "string"),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_json_for_named_enum!(ProgrammingInterface, CoreArchitecture, Family);
+
+/// Implements the conversion for a fieldless enum, using a fixed name for
+/// each variant.
+macro_rules! impl_json_for_enum {
+    ($($ty:ident { $($variant:ident => $name:expr),* })*) => {
+        $(
+            impl ToJson for $ty {
+                fn to_json_value(&self) -> JsonValue {
+                    match self {
+                        $( $ty::$variant => $name.into(), )*
+                    }
+                }
+            }
+
+            impl FromJson for $ty {
+                fn from_json_value(value: &JsonValue, path: &str) -> Result<Self, JsonError> {
+                    match value.as_str() {
+                        $( Some($name) => Ok($ty::$variant), )*
+                        _ => expected(path, concat!("a ", stringify!($ty))),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_json_for_enum! {
+    ReadWrite {
+        ReadAndWrite => "read_and_write",
+        ReadOnly => "read_only",
+        WriteOnly => "write_only",
+        NoAccess => "no_access"
+    }
+    Architecture {
+        Unknown => "unknown",
+        Avr0 => "avr0",
+        Avr1 => "avr1",
+        Avr2 => "avr2",
+        Avr25 => "avr25",
+        Avr3 => "avr3",
+        Avr31 => "avr31",
+        Avr35 => "avr35",
+        Avr4 => "avr4",
+        Avr5 => "avr5",
+        Avr51 => "avr51",
+        Avr6 => "avr6",
+        Xmega2 => "xmega2",
+        Xmega3 => "xmega3",
+        Xmega4 => "xmega4",
+        Xmega5 => "xmega5",
+        Xmega6 => "xmega6",
+        Xmega7 => "xmega7",
+        Tiny => "tiny"
+    }
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if !self.path.is_empty() {
+            write!(fmt, "{}: ", self.path)?;
+        }
+        match &self.kind {
+            JsonErrorKind::Syntax(e) => write!(fmt, "invalid JSON: {}", e),
+            JsonErrorKind::Expected(what) => write!(fmt, "expected {}", what),
+        }
+    }
+}
+
+impl Error for JsonError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.kind {
+            JsonErrorKind::Syntax(e) => Some(e),
+            JsonErrorKind::Expected(..) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(all(feature = "family-atmega", feature = "family-tiny", feature = "family-xmega"))]
+    fn mcus_round_trip_through_json() {
+        for name in ["atmega328p", "attiny816", "atxmega128a1"].iter() {
+            let mcu = ::microcontroller(name);
+            assert_eq!(mcu, &Mcu::from_json(&mcu.to_json()).unwrap());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "family-tiny")]
+    fn json_uses_field_names() {
+        let json = json::parse(&::microcontroller("attiny85").to_json()).unwrap();
+        assert_eq!("ATtiny85", json["device"]["name"]);
+        assert_eq!("AVR8", json["device"]["architecture"]);
+        assert_eq!("avr25", json["architecture"]);
+        assert_eq!(1.8, json["variants"][0]["voltage_min"]);
+    }
+
+    #[test]
+    fn invalid_json_is_an_error() {
+        let error = Mcu::from_json(r#"{"device": {"name": 1}}"#).unwrap_err();
+        assert_eq!("device.name", error.path);
+
+        let error = Mcu::from_json("{").unwrap_err();
+        assert!(matches!(error.kind, JsonErrorKind::Syntax(..)));
+    }
+
+    #[test]
+    #[cfg(feature = "family-tiny")]
+    fn json_files_are_written() {
+        let dir = ::tempfile::TempDir::new().unwrap();
+        let mcus = [::microcontroller("attiny85").clone()];
+        write_json_files(&mcus, dir.path()).unwrap();
+
+        let body = fs::read_to_string(dir.path().join("attiny85.json")).unwrap();
+        assert_eq!(mcus[0], Mcu::from_json(&body).unwrap());
+    }
+}
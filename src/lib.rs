@@ -28,6 +28,36 @@
 //!
 //! A build script takes these packfiles and persists them as data structures in Rust.
 //!
+//! # Features
+//!
+//! Each device family has a cargo feature, all of which are enabled by default.
+//! Only the microcontrollers of the enabled families are embedded.
+//!
+//! * `family-atmega`
+//! * `family-tiny`
+//! * `family-xmega`
+//! * `family-automotive`
+//!
+//! The optional `serde` feature implements `Serialize` and `Deserialize` for
+//! the device model.
+//!
+//! The `std` feature, also enabled by default, provides the full device model.
+//! Without it, the crate is `no_std` and only provides the compact
+//! [`descriptors`](descriptors/index.html), which can be used by firmware
+//! running on the microcontroller itself.
+//!
+//! ```nodoc
+//! const MCU: avr_mcu::descriptors::Descriptor = avr_mcu::device!("atmega328p");
+//! ```
+//!
+//! The family features do not enable `std`. Crates that disable the default
+//! features to select families, but still use the device model, have to enable
+//! `std` themselves:
+//!
+//! ```toml
+//! avr-mcu = { version = "0.3", default-features = false, features = ["std", "family-tiny"] }
+//! ```
+//!
 //! # Examples
 //!
 //! ```nodoc
@@ -36,16 +66,66 @@
 //! }
 //! ```
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+extern crate json;
+#[cfg(feature = "std")]
 extern crate xmltree;
+#[cfg(feature = "std")]
+extern crate zip;
+#[cfg(feature = "std")]
 #[macro_use]
 extern crate lazy_static;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(test)]
+extern crate tempfile;
 
-pub use self::load::{microcontroller, microcontroller_names, microcontrollers};
+#[cfg(feature = "std")]
+pub use self::atpack::{AtPack, AtPackDevice};
+#[cfg(feature = "std")]
+pub use self::error::{PackError, PackErrorKind};
+#[cfg(feature = "std")]
+pub use self::fuses::{FuseError, FuseSetting, FuseValue, Fuses};
+#[cfg(feature = "std")]
+pub use self::json_format::{write_json_files, JsonError, JsonErrorKind};
+#[cfg(feature = "std")]
+pub use self::load::{
+    microcontroller, microcontroller_names, microcontrollers, try_microcontroller, DuplicatePolicy,
+    PackLoader, UnknownMcuError,
+};
+#[cfg(feature = "std")]
+pub use self::lock_bits::{LockBits, LockState, ProtectedMemory, Protection};
+#[cfg(feature = "std")]
 pub use self::model::*;
+#[cfg(feature = "std")]
+pub use self::query::{McuQuery, QueryMatch, RankKey};
 
+#[cfg(feature = "std")]
+mod atpack;
+#[cfg(feature = "std")]
+mod codec;
+#[cfg(feature = "std")]
+mod error;
+#[cfg(feature = "std")]
 mod extra_info;
+#[cfg(feature = "std")]
+mod fuses;
+#[cfg(feature = "std")]
+mod json_format;
+#[cfg(feature = "std")]
 mod load;
+#[cfg(feature = "std")]
+mod lock_bits;
+#[cfg(feature = "std")]
 mod model;
+#[cfg(feature = "std")]
 mod pack;
+#[cfg(feature = "std")]
+mod query;
 
+#[macro_use]
+pub mod descriptors;
+#[cfg(feature = "std")]
 pub mod current;
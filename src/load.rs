@@ -1,33 +1,80 @@
+use atpack::AtPack;
+use codec;
+use error::{PackError, PackErrorKind};
 use model::Mcu;
 use pack;
 
+use std::collections::HashMap;
+use std::error::Error;
+use std::hash::Hash;
 use std::path::{Path, PathBuf};
-use std::{fs, io};
+use std::{fmt, fs, io};
 
 /// The extension on the pack files.
 const PACK_FILE_EXT: &str = "atdf";
 
-/// All pack collections inside the 'packs' folder
-/// of this repository.
-const PACK_COLLECTIONS: &[&str] =
-    &["atmega", "tiny", "xmegaa", "xmegab", "xmegac", "xmegad", "xmegae", "automotive"];
-
-/// The on-disk path of the crate root.
-const CRATE_ROOT: &str = env!("CARGO_MANIFEST_DIR");
+/// All microcontrollers, parsed from the packfiles and encoded by the build script.
+static ENCODED_MCUS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/microcontrollers.bin"));
 
 lazy_static! {
-    static ref MCUS: Vec<Mcu> =
-        self::load_microcontrollers().expect("failed to load microcontrollers");
-    static ref MCU_NAMES: Vec<String> = pack_informations()
-        .expect("could not find packfiles")
-        .into_iter()
-        .map(|pack| pack.mcu_name)
-        .collect();
+    static ref MCUS: Vec<Mcu> = codec::decode(ENCODED_MCUS);
+    static ref MCU_NAMES: Vec<String> =
+        microcontrollers().iter().map(|mcu| mcu.device.name.to_lowercase()).collect();
+    /// The index of each microcontroller in `MCUS`, by lowercase name.
+    static ref MCU_INDICES: HashMap<&'static str, usize> =
+        MCU_NAMES.iter().enumerate().map(|(index, name)| (name.as_str(), index)).collect();
+}
+
+/// Loads microcontrollers from pack files in arbitrary locations.
+///
+/// This makes it possible to use newer or patched pack files than the ones
+/// that ship with this crate.
+///
+/// # Examples
+///
+/// ```nodoc
+/// let mcus = avr_mcu::PackLoader::new()
+///     .builtin_packs()
+///     .atpack("Microchip.AVR-Dx_DFP.2.2.253.atpack")
+///     .directory("/opt/microchip/packs/ATtiny_DFP/atdf")
+///     .file("patches/ATmega328P.atdf")
+///     .load()?;
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackLoader {
+    sources: Vec<PackSource>,
+    recursive: bool,
+    duplicate_policy: DuplicatePolicy,
+}
+
+/// A place to look for pack files.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum PackSource {
+    /// The devices embedded into this crate.
+    Builtin,
+    Directory(PathBuf),
+    File(PathBuf),
+    AtPack(PathBuf),
+}
+
+/// An error returned when there is no microcontroller with a given name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownMcuError {
+    /// The name that was looked up.
+    pub name: String,
 }
 
-struct PackInfo {
-    pub mcu_name: String,
-    pub path: PathBuf,
+/// What to do when several pack files describe a device with the same name.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DuplicatePolicy {
+    /// The device from the pack file loaded last replaces the earlier one.
+    Override,
+    /// The devices are merged.
+    ///
+    /// Items from the pack file loaded last, such as variants, modules and
+    /// peripherals, replace the items with the same name in the earlier device.
+    /// Items that only exist in one of the pack files are kept.
+    Merge,
 }
 
 /// Retrieves a list of `Mcu` objects for all microcontrollers.
@@ -46,53 +93,194 @@ pub fn microcontroller_names() -> &'static [String] {
 }
 
 /// Retrieves information for a specific microcontroller.
-pub fn microcontroller(name: &str) -> Mcu {
-    let pack_info = pack_informations()
-        .unwrap()
-        .into_iter()
-        .find(|pack_info| pack_info.mcu_name == name)
-        .expect(&format!("no microcontroller with the name '{}' found", name));
-    pack::load(&pack_info.path).expect("could not parse microcontroller pack")
+///
+/// # Panics
+///
+/// Panics if there is no microcontroller with the name. See `try_microcontroller`.
+pub fn microcontroller(name: &str) -> &'static Mcu {
+    try_microcontroller(name).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Retrieves information for a specific microcontroller.
+///
+/// The name is not case sensitive. Returns an error if there is no
+/// microcontroller with the name.
+pub fn try_microcontroller(name: &str) -> Result<&'static Mcu, UnknownMcuError> {
+    MCU_INDICES
+        .get(name.to_lowercase().as_str())
+        .map(|&index| &microcontrollers()[index])
+        .ok_or_else(|| UnknownMcuError { name: name.to_owned() })
 }
 
-/// Retrieves a list of `Mcu` objects in a directory containg `PACK_COLLECTIONS`.
-fn load_microcontrollers() -> Result<Vec<Mcu>, io::Error> {
-    let microcontrollers = pack_informations()?
-        .into_iter()
-        .map(|pack_info| pack::load(&pack_info.path).unwrap())
-        .collect();
+impl PackLoader {
+    /// Creates a loader without any pack files.
+    pub fn new() -> Self {
+        PackLoader {
+            sources: Vec::new(),
+            recursive: false,
+            duplicate_policy: DuplicatePolicy::Override,
+        }
+    }
+
+    /// Adds the devices that ship with this crate.
+    ///
+    /// These are the devices returned by `microcontrollers`, which are
+    /// embedded into this crate, so no pack files are read for them.
+    pub fn builtin_packs(mut self) -> Self {
+        self.sources.push(PackSource::Builtin);
+        self
+    }
+
+    /// Adds all pack files in a directory.
+    pub fn directory<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.sources.push(PackSource::Directory(path.into()));
+        self
+    }
+
+    /// Adds a single pack file.
+    pub fn file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.sources.push(PackSource::File(path.into()));
+        self
+    }
+
+    /// Adds all devices in an `.atpack` archive.
+    pub fn atpack<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.sources.push(PackSource::AtPack(path.into()));
+        self
+    }
+
+    /// Sets whether subdirectories of the added directories are searched.
+    ///
+    /// This is off by default.
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Sets what happens when several pack files describe the same device.
+    ///
+    /// Pack files are loaded in the order their sources were added. This
+    /// defaults to `DuplicatePolicy::Override`.
+    pub fn duplicate_policy(mut self, policy: DuplicatePolicy) -> Self {
+        self.duplicate_policy = policy;
+        self
+    }
+
+    /// Gets the paths of all loose pack files that will be loaded, in order.
+    ///
+    /// Pack archives and the built-in devices are not included.
+    pub fn pack_paths(&self) -> Result<Vec<PathBuf>, PackError> {
+        let mut paths = Vec::new();
+        for source in self.sources.iter() {
+            paths.extend(self.source_paths(source)?);
+        }
+        Ok(paths)
+    }
+
+    /// Loads all pack files.
+    pub fn load(&self) -> Result<Vec<Mcu>, PackError> {
+        let mut mcus: Vec<Mcu> = Vec::new();
+
+        for source in self.sources.iter() {
+            let loaded = match source {
+                PackSource::Builtin => microcontrollers().to_vec(),
+                PackSource::AtPack(path) => AtPack::open(path.clone())?.microcontrollers()?,
+                _ => self
+                    .source_paths(source)?
+                    .iter()
+                    .map(|path| pack::load(path))
+                    .collect::<Result<_, _>>()?,
+            };
+            for mcu in loaded {
+                self.add(&mut mcus, mcu);
+            }
+        }
+        Ok(mcus)
+    }
+
+    /// Gets the paths of the loose pack files in a source.
+    fn source_paths(&self, source: &PackSource) -> Result<Vec<PathBuf>, PackError> {
+        match source {
+            PackSource::File(path) => Ok(vec![path.clone()]),
+            PackSource::Directory(path) => {
+                let mut packs = find_packs(path, self.recursive)
+                    .map_err(|e| PackError::new(path.clone(), PackErrorKind::Io(e)))?;
+                packs.sort();
+                Ok(packs)
+            }
+            PackSource::Builtin | PackSource::AtPack(..) => Ok(Vec::new()),
+        }
+    }
 
-    Ok(microcontrollers)
+    /// Adds a loaded device, handling duplicates.
+    fn add(&self, mcus: &mut Vec<Mcu>, mcu: Mcu) {
+        match mcus.iter().position(|m| m.device.name == mcu.device.name) {
+            Some(index) => match self.duplicate_policy {
+                DuplicatePolicy::Override => mcus[index] = mcu,
+                DuplicatePolicy::Merge => merge(&mut mcus[index], mcu),
+            },
+            None => mcus.push(mcu),
+        }
+    }
 }
 
-fn pack_informations() -> Result<Vec<PackInfo>, io::Error> {
-    let path = Path::new(CRATE_ROOT).join("packs");
-    pack_informations_from(&path)
+impl fmt::Display for UnknownMcuError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "no microcontroller with the name '{}' found", self.name)
+    }
 }
 
-fn pack_informations_from(path: &Path) -> Result<Vec<PackInfo>, io::Error> {
-    let mut pack_paths = Vec::new();
+impl Error for UnknownMcuError {}
 
-    for pack_name in PACK_COLLECTIONS {
-        pack_paths.extend(find_packs(&path.join(pack_name)).unwrap());
+impl Default for PackLoader {
+    fn default() -> Self {
+        PackLoader::new()
     }
+}
+
+/// Merges a newer description of a device into an older one.
+fn merge(mcu: &mut Mcu, newer: Mcu) {
+    let device = &mut mcu.device;
 
-    Ok(pack_paths
-        .into_iter()
-        .map(|path| PackInfo {
-            mcu_name: path.file_stem().unwrap().to_str().unwrap().to_lowercase().to_owned(),
-            path: path.to_owned(),
-        })
-        .collect())
+    merge_by(&mut device.address_spaces, newer.device.address_spaces, |a| a.id.clone());
+    merge_by(&mut device.peripherals, newer.device.peripherals, |p| p.name.clone());
+    merge_by(&mut device.interrupts, newer.device.interrupts, |i| i.index);
+    merge_by(&mut device.property_groups, newer.device.property_groups, |g| g.name.clone());
+    merge_by(&mut device.interfaces, newer.device.interfaces, |i| i.clone());
+    device.parameters.extend(newer.device.parameters);
+    device.architecture = newer.device.architecture;
+    device.family = newer.device.family;
+
+    merge_by(&mut mcu.variants, newer.variants, |v| v.name.clone());
+    merge_by(&mut mcu.modules, newer.modules, |m| m.name.clone());
+    merge_by(&mut mcu.pinouts, newer.pinouts, |p| p.name.clone());
+    mcu.architecture = newer.architecture;
+    mcu.c_preprocessor_name = newer.c_preprocessor_name;
+}
+
+/// Merges two lists, replacing items that share a key.
+fn merge_by<T, K, F>(items: &mut Vec<T>, newer: Vec<T>, key: F)
+where
+    K: PartialEq,
+    F: Fn(&T) -> K,
+{
+    for item in newer {
+        match items.iter().position(|existing| key(existing) == key(&item)) {
+            Some(index) => items[index] = item,
+            None => items.push(item),
+        }
+    }
 }
 
 /// Finds all pack files in a directory.
-fn find_packs(in_dir: &Path) -> Result<Vec<PathBuf>, io::Error> {
+fn find_packs(in_dir: &Path, recursive: bool) -> Result<Vec<PathBuf>, io::Error> {
     let mut paths = Vec::new();
 
     for entry in fs::read_dir(in_dir)? {
         let entry = entry?;
-        if let Some(PACK_FILE_EXT) = entry.path().extension().map(|s| s.to_str().unwrap()) {
+        if recursive && entry.file_type()?.is_dir() {
+            paths.extend(find_packs(&entry.path(), recursive)?);
+        } else if let Some(PACK_FILE_EXT) = entry.path().extension().and_then(|s| s.to_str()) {
             paths.push(entry.path());
         }
     }
@@ -101,15 +289,101 @@ fn find_packs(in_dir: &Path) -> Result<Vec<PathBuf>, io::Error> {
 
 #[cfg(test)]
 mod test {
+    use super::*;
+
+    fn packs_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("packs")
+    }
+
     #[test]
+    #[cfg(feature = "family-atmega")]
     fn there_are_at_least_100_microcontrollers() {
         let mcus = super::microcontrollers();
         assert!(mcus.len() > 100, "there should be at least 100 microcontrollers");
     }
 
     #[test]
+    #[cfg(feature = "family-atmega")]
     fn can_get_atmega328p_by_name() {
         let mcu = super::microcontroller("atmega328p");
         assert_eq!("ATmega328P", mcu.device.name);
     }
+
+    #[test]
+    #[cfg(feature = "family-atmega")]
+    fn embedded_microcontrollers_match_packfiles() {
+        let path = packs_dir().join("atmega").join("ATmega328P.atdf");
+        assert_eq!(&pack::load(&path).unwrap(), super::microcontroller("atmega328p"));
+    }
+
+    #[test]
+    #[cfg(feature = "family-atmega")]
+    fn microcontrollers_are_shared() {
+        let mcu = super::microcontroller("ATmega328P");
+        assert!(::std::ptr::eq(mcu, super::microcontroller("atmega328p")));
+    }
+
+    #[test]
+    fn unknown_microcontrollers_are_errors() {
+        let error = super::try_microcontroller("atmega9000").unwrap_err();
+        assert_eq!("atmega9000", error.name);
+        assert_eq!("no microcontroller with the name 'atmega9000' found", error.to_string());
+    }
+
+    #[test]
+    fn pack_loader_loads_directories() {
+        let tiny = PackLoader::new().directory(packs_dir().join("tiny")).load().unwrap();
+        assert!(tiny.iter().any(|mcu| mcu.device.name == "ATtiny85"));
+        assert!(!tiny.iter().any(|mcu| mcu.device.name == "ATmega328P"));
+
+        let all = PackLoader::new().directory(packs_dir()).recursive(true).pack_paths().unwrap();
+        assert!(all.iter().any(|path| path.ends_with("tiny/ATtiny85.atdf")));
+    }
+
+    #[test]
+    fn pack_loader_starts_from_builtin_devices() {
+        let loader = PackLoader::new().builtin_packs();
+        assert_eq!(microcontrollers(), &loader.load().unwrap()[..]);
+        assert!(loader.pack_paths().unwrap().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "family-tiny")]
+    fn pack_loader_applies_packs_to_builtin_devices() {
+        let attiny85 = packs_dir().join("tiny").join("ATtiny85.atdf");
+        let mcus = PackLoader::new()
+            .builtin_packs()
+            .file(attiny85)
+            .duplicate_policy(DuplicatePolicy::Merge)
+            .load()
+            .unwrap();
+        assert_eq!(microcontrollers(), &mcus[..]);
+    }
+
+    #[test]
+    fn pack_loader_overrides_duplicates() {
+        let attiny85 = packs_dir().join("tiny").join("ATtiny85.atdf");
+        let mcus = PackLoader::new().file(&attiny85).file(&attiny85).load().unwrap();
+        assert_eq!(1, mcus.len());
+    }
+
+    #[test]
+    #[cfg(feature = "family-tiny")]
+    fn pack_loader_merges_duplicates() {
+        let mut mcu = microcontroller("attiny85").clone();
+        let mut newer = mcu.clone();
+        newer.variants.truncate(1);
+        newer.variants[0].speed_max_hz = 1;
+        newer.variants.push(super::super::Variant {
+            name: "ATtiny85-NEW".to_owned(),
+            ..newer.variants[0].clone()
+        });
+
+        let variant_count = mcu.variants.len();
+        merge(&mut mcu, newer);
+
+        assert_eq!(variant_count + 1, mcu.variants.len());
+        assert_eq!(1, mcu.variants[0].speed_max_hz);
+        assert_eq!("ATtiny85-NEW", mcu.variants.last().unwrap().name);
+    }
 }
@@ -0,0 +1,289 @@
+//! Decoding and encoding of lock bytes.
+//!
+//! Lock bits are described by the `LOCKBIT` module of a microcontroller.
+//! Classic and XMEGA parts have two-bit modes for the whole memory (`LB`)
+//! and for each flash section (`BLB0`, `BLB1` and friends), where a bit is
+//! programmed when it is 0. AVR8X parts instead have a single lock key byte.
+//!
+//! Lock bits can only be cleared by a chip erase.
+
+use fuses::{ConfigLayout, FuseError, FuseSetting, FuseValue};
+use model::{CoreArchitecture, Mcu};
+
+use std::fmt;
+
+/// The lock key of AVR8X parts that leaves the part unlocked.
+///
+/// Every other key locks the part.
+const UNLOCKED_KEY: u32 = 0xC5;
+
+/// The lock bit layout of a microcontroller.
+///
+/// # Examples
+///
+/// ```nodoc
+/// use avr_mcu::FuseValue;
+///
+/// let lock_bits = avr_mcu::microcontroller("atmega328p").lock_bits().unwrap();
+/// let bytes = lock_bits.encode(&[("BLB1", FuseValue::Named("SPM_DISABLE"))]).unwrap();
+///
+/// let state = lock_bits.decode(&bytes).unwrap();
+/// for protection in state.protections.iter() {
+///     println!("{}", protection);
+/// }
+/// assert!(state.requires_chip_erase());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockBits<'a> {
+    layout: ConfigLayout<'a>,
+    is_key: bool,
+}
+
+/// The lock settings decoded from lock bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockState<'a> {
+    /// The raw settings, such as `LB` and `BLB0`.
+    pub settings: Vec<FuseSetting<'a>>,
+    /// The protection of each memory that has lock bits, in the order of
+    /// `ProtectedMemory`.
+    pub protections: Vec<Protection>,
+}
+
+/// The protection of a memory.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Protection {
+    /// The protected memory.
+    pub memory: ProtectedMemory,
+    /// Whether the memory cannot be read.
+    pub read: bool,
+    /// Whether the memory cannot be written.
+    pub write: bool,
+}
+
+/// A memory that can be protected by lock bits.
+#[derive(Copy, Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub enum ProtectedMemory {
+    /// The flash and EEPROM, as accessed by a programmer.
+    ///
+    /// Write protection also prevents changing the fuses.
+    FlashAndEeprom,
+    /// The application section of the flash, as accessed by `LPM` and `SPM`.
+    ApplicationSection,
+    /// The application table section of the flash, as accessed by `LPM` and `SPM`.
+    ApplicationTableSection,
+    /// The boot section of the flash, as accessed by `LPM` and `SPM`.
+    BootSection,
+}
+
+impl Mcu {
+    /// Gets the lock bit layout, if the microcontroller has lock bits.
+    pub fn lock_bits(&self) -> Option<LockBits<'_>> {
+        Some(LockBits {
+            layout: ConfigLayout::new(self, "LOCKBIT")?,
+            is_key: self.device.architecture == CoreArchitecture::Avr8X,
+        })
+    }
+}
+
+impl<'a> LockBits<'a> {
+    /// Gets the number of lock bytes.
+    pub fn len(&self) -> usize {
+        self.layout.len
+    }
+
+    /// Checks if there are no lock bytes.
+    pub fn is_empty(&self) -> bool {
+        self.layout.len == 0
+    }
+
+    /// Checks if the lock bits are a single key byte, as on AVR8X parts.
+    pub fn is_key(&self) -> bool {
+        self.is_key
+    }
+
+    /// Gets the default lock bytes, which leave the part unlocked.
+    pub fn defaults(&self) -> Vec<u8> {
+        let unlocked = if self.is_key { UNLOCKED_KEY as u8 } else { 0xFF };
+        self.layout.defaults(unlocked)
+    }
+
+    /// Decodes lock bytes into settings and the protection they give.
+    pub fn decode(&self, bytes: &[u8]) -> Result<LockState<'a>, FuseError> {
+        let mut settings = Vec::new();
+        let mut protections = Vec::new();
+        for (register, bitfield, value) in self.layout.fields(bytes)? {
+            let named_value = bitfield.and_then(|b| self.layout.named_value(b, value));
+            settings.push(FuseSetting { register, bitfield, value, named_value, programmed: None });
+
+            if let Some(protection) = bitfield.and_then(|b| self.protection(&b.name, value)) {
+                protections.push(protection);
+            }
+        }
+        protections.sort_by_key(|p| p.memory);
+
+        Ok(LockState { settings, protections })
+    }
+
+    /// Encodes settings into lock bytes, starting from the defaults.
+    ///
+    /// Settings are named by their bitfield, such as `BLB0`, and values by the
+    /// entries of their value groups, such as `SPM_DISABLE`. Names are not
+    /// case sensitive.
+    pub fn encode(&self, settings: &[(&str, FuseValue)]) -> Result<Vec<u8>, FuseError> {
+        let mut bytes = self.defaults();
+        for &(name, value) in settings {
+            let (register, bitfield) = self.layout.find(name)?;
+            let raw = match value {
+                FuseValue::Programmed(..) => return Err(FuseError::NotASingleBit(name.to_owned())),
+                FuseValue::Named(value) => self.layout.value_by_name(name, bitfield, value)?,
+                FuseValue::Raw(value) => value,
+            };
+            self.layout.write(&mut bytes, name, register, bitfield, raw)?;
+        }
+        Ok(bytes)
+    }
+
+    /// Gets the protection that a lock bitfield gives.
+    fn protection(&self, bitfield: &str, value: u32) -> Option<Protection> {
+        let memory = match bitfield {
+            "LB" => ProtectedMemory::FlashAndEeprom,
+            "BLB0" | "BLBA" | "AP" => ProtectedMemory::ApplicationSection,
+            "BLBAT" => ProtectedMemory::ApplicationTableSection,
+            "BLB1" | "BLBB" | "BLP" => ProtectedMemory::BootSection,
+            _ => return None,
+        };
+
+        let (read, write) = if self.is_key {
+            (value != UNLOCKED_KEY, value != UNLOCKED_KEY)
+        } else if memory == ProtectedMemory::FlashAndEeprom {
+            // Programming the low bit disables writes, programming both also disables reads.
+            (value & 0b10 == 0, value != 0b11)
+        } else {
+            (value & 0b10 == 0, value & 0b01 == 0)
+        };
+        Some(Protection { memory, read, write })
+    }
+}
+
+impl<'a> LockState<'a> {
+    /// Gets the protection of a memory, if it has lock bits.
+    pub fn protection(&self, memory: ProtectedMemory) -> Option<&Protection> {
+        self.protections.iter().find(|p| p.memory == memory)
+    }
+
+    /// Checks if only a chip erase can undo the lock settings.
+    ///
+    /// This is the case as soon as any memory is protected, as lock bits can
+    /// only be cleared by a chip erase. A chip erase also erases the flash and,
+    /// unless the `EESAVE` fuse is programmed, the EEPROM.
+    pub fn requires_chip_erase(&self) -> bool {
+        self.protections.iter().any(|p| p.read || p.write)
+    }
+}
+
+impl ProtectedMemory {
+    /// Gets a description of the memory.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProtectedMemory::FlashAndEeprom => "flash and EEPROM",
+            ProtectedMemory::ApplicationSection => "application section",
+            ProtectedMemory::ApplicationTableSection => "application table section",
+            ProtectedMemory::BootSection => "boot section",
+        }
+    }
+}
+
+impl fmt::Display for Protection {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let protection = match (self.read, self.write) {
+            (false, false) => "not protected",
+            (true, false) => "read-protected",
+            (false, true) => "write-protected",
+            (true, true) => "read- and write-protected",
+        };
+        write!(fmt, "{}: {}", self.memory.name(), protection)
+    }
+}
+
+#[cfg(all(
+    test,
+    any(feature = "family-atmega", feature = "family-tiny", feature = "family-xmega")
+))]
+mod test {
+    use super::*;
+
+    fn lock_bits(name: &str) -> LockBits<'static> {
+        ::microcontroller(name).lock_bits().unwrap()
+    }
+
+    #[test]
+    #[cfg(feature = "family-atmega")]
+    fn atmega328p_lock_bits_are_decoded() {
+        let lock_bits = lock_bits("atmega328p");
+        assert_eq!(vec![0xFF], lock_bits.defaults());
+
+        let state = lock_bits.decode(&[0xFF]).unwrap();
+        assert_eq!(3, state.protections.len());
+        assert!(!state.requires_chip_erase());
+
+        // LB = PROG_DISABLED, BLB0 = LPM_DISABLE, BLB1 = NO_LOCK
+        let state = lock_bits.decode(&[0xF6]).unwrap();
+        let flash = state.protection(ProtectedMemory::FlashAndEeprom).unwrap();
+        assert_eq!((false, true), (flash.read, flash.write));
+        assert_eq!(
+            "application section: read-protected",
+            state.protection(ProtectedMemory::ApplicationSection).unwrap().to_string()
+        );
+        assert_eq!(
+            "boot section: not protected",
+            state.protection(ProtectedMemory::BootSection).unwrap().to_string()
+        );
+        assert_eq!(Some("PROG_DISABLED"), state.settings[0].named_value.map(|v| &v.name[..]));
+        assert!(state.requires_chip_erase());
+    }
+
+    #[test]
+    #[cfg(feature = "family-atmega")]
+    fn atmega328p_lock_bits_are_encoded() {
+        let lock_bits = lock_bits("atmega328p");
+        let bytes = lock_bits
+            .encode(&[("LB", FuseValue::Named("prog_ver_disabled")), ("BLB1", FuseValue::Raw(2))])
+            .unwrap();
+        assert_eq!(vec![0xEC], bytes);
+
+        let state = lock_bits.decode(&bytes).unwrap();
+        let boot = state.protection(ProtectedMemory::BootSection).unwrap();
+        assert_eq!((false, true), (boot.read, boot.write));
+
+        assert_eq!(
+            Err(FuseError::NotASingleBit("LB".to_owned())),
+            lock_bits.encode(&[("LB", FuseValue::Programmed(true))])
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "family-xmega")]
+    fn xmega_lock_bits_have_an_application_table_section() {
+        // Older XMEGA packfiles put the lock bits into the NVM peripheral.
+        let state = lock_bits("atxmega128a1").decode(&[0xF3]).unwrap();
+        let table = state.protection(ProtectedMemory::ApplicationTableSection).unwrap();
+        assert_eq!((true, true), (table.read, table.write));
+        assert_eq!(4, state.protections.len());
+    }
+
+    #[test]
+    #[cfg(feature = "family-tiny")]
+    fn avr8x_lock_bits_are_a_key() {
+        let lock_bits = lock_bits("attiny816");
+        assert!(lock_bits.is_key());
+        assert_eq!(vec![0xC5], lock_bits.defaults());
+        assert!(!lock_bits.decode(&[0xC5]).unwrap().requires_chip_erase());
+
+        let bytes = lock_bits.encode(&[("LB", FuseValue::Named("RWLOCK"))]).unwrap();
+        assert_eq!(vec![0x3A], bytes);
+        let state = lock_bits.decode(&[0x00]).unwrap();
+        let flash = state.protection(ProtectedMemory::FlashAndEeprom).unwrap();
+        assert_eq!((true, true), (flash.read, flash.write));
+        assert!(state.requires_chip_erase());
+    }
+}
@@ -1,5 +1,14 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The names of the data memory segments that hold the internal SRAM, in
+/// order of preference.
+const INTERNAL_SRAM_SEGMENTS: &[&str] = &["IRAM", "INTERNAL_SRAM", "SRAM"];
+
 /// A microcontroller with one or more variants.
 #[derive(Clone, Debug, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Mcu {
     /// Information about the microcontroller itself.
     pub device: Device,
@@ -7,6 +16,8 @@ pub struct Mcu {
     pub variants: Vec<Variant>,
     /// The modules built into the mcu package.
     pub modules: Vec<Module>,
+    /// The pin layouts of the packages the mcu comes in.
+    pub pinouts: Vec<Pinout>,
     /// The family that the mcu belongs to.
     pub architecture: Architecture,
     /// The C preprocessor name.
@@ -15,19 +26,31 @@ pub struct Mcu {
 
 /// Information fore a specific device.
 #[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Device {
     /// The name of the device.
     pub name: String,
+    /// The core architecture of the device.
+    pub architecture: CoreArchitecture,
+    /// The product family of the device.
+    pub family: Family,
     /// A list of all address spaces the device has.
     pub address_spaces: Vec<AddressSpace>,
     /// A list of supported peripherals.
     pub peripherals: Vec<Peripheral>,
     /// A list of supported interrupts
     pub interrupts: Vec<Interrupt>,
+    /// Device properties, such as signatures and programming parameters.
+    pub property_groups: Vec<PropertyGroup>,
+    /// The programming and debugging interfaces the device supports.
+    pub interfaces: Vec<ProgrammingInterface>,
+    /// Device-wide parameters, such as `SUPPORTS_EEPROM_ERASE`.
+    pub parameters: BTreeMap<String, String>,
 }
 
 /// A variation of a specific microcontroller.
 #[derive(Clone, Debug, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Variant {
     /// The name of the variant.
     pub name: String,
@@ -47,8 +70,34 @@ pub struct Variant {
     pub speed_max_hz: u64,
 }
 
+/// The physical pin layout of a package.
+#[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Pinout {
+    /// The name of the pinout, for example `TQFP32`.
+    pub name: String,
+    /// A description of the pinout.
+    pub caption: String,
+    /// The pins on the package.
+    pub pins: Vec<Pin>,
+}
+
+/// A physical pin on a package.
+#[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Pin {
+    /// The position of the pin on the package.
+    ///
+    /// This is usually a number, but ball grid arrays use
+    /// positions such as `A1`.
+    pub position: String,
+    /// The name of the pad connected to the pin, such as `PB5` or `VCC`.
+    pub pad: String,
+}
+
 /// An address space.
 #[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct AddressSpace {
     /// The identifier.
     pub id: String,
@@ -64,6 +113,7 @@ pub struct AddressSpace {
 
 /// A segment of memory in a particular address space.
 #[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MemorySegment {
     /// The name of the segment.
     pub name: String,
@@ -85,6 +135,7 @@ pub struct MemorySegment {
 
 /// An on-board peripheral, such as an IO port.
 #[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Peripheral {
     /// The name of the peripheral, for example, `PORT`.
     pub name: String,
@@ -98,6 +149,7 @@ pub struct Peripheral {
 
 /// An interrupt supported by a device.
 #[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Interrupt {
     /// The name of the interrupt, for example `TIMER1_COMPA`.
     pub name: String,
@@ -107,8 +159,59 @@ pub struct Interrupt {
     pub index: u32,
 }
 
+/// A named group of device properties, for example `SIGNATURES`.
+#[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PropertyGroup {
+    /// The name of the group, for example `ISP_INTERFACE`.
+    pub name: String,
+    /// The properties in the group.
+    pub properties: Vec<Property>,
+}
+
+/// A device property, such as `SIGNATURE0`.
+///
+/// Property values are kept in their textual form. Use the typed
+/// accessors to interpret them.
+#[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Property {
+    /// The name of the property.
+    pub name: String,
+    /// The raw value of the property, for example `0x1e` or `PA1`.
+    pub value: String,
+}
+
+/// An interface that can be used to program or debug a device.
+#[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "String", into = "String"))]
+pub enum ProgrammingInterface {
+    /// In-system programming over SPI.
+    Isp,
+    /// High-voltage parallel programming.
+    Hvpp,
+    /// High-voltage serial programming.
+    Hvsp,
+    /// debugWIRE on-chip debugging.
+    DebugWire,
+    /// JTAG on megaAVR devices.
+    MegaJtag,
+    /// JTAG on AVR XMEGA devices.
+    XmegaJtag,
+    /// Program and Debug Interface.
+    Pdi,
+    /// Unified Program and Debug Interface.
+    Updi,
+    /// Tiny Programming Interface.
+    Tpi,
+    /// An interface type this crate does not know about.
+    Unknown(String),
+}
+
 /// A module built into the silicon.
 #[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Module {
     /// The name of the module, for example, `PORT`.
     pub name: String,
@@ -116,29 +219,97 @@ pub struct Module {
     pub register_groups: Vec<RegisterGroup>,
     /// Value groups associated with the module.
     pub value_groups: Vec<ValueGroup>,
+    /// Module parameters, such as `CORE_VERSION`.
+    pub parameters: BTreeMap<String, String>,
 }
 
 /// An instance of a peripheral.
 #[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Instance {
     /// The name of the peripheral instance, for example, `PORTB`.
     pub name: String,
+    /// A description of the instance.
+    pub caption: String,
+    /// The register groups that control the instance.
+    ///
+    /// These refer to register groups of the module with the same name as the
+    /// peripheral, and locate them in an address space.
+    pub register_groups: Vec<RegisterGroupRef>,
     /// What signals are used in the peripheral.
     pub signals: Vec<Signal>,
+    /// Instance parameters, such as `CORE_VERSION`.
+    pub parameters: BTreeMap<String, String>,
+}
+
+/// A register of a peripheral instance, located at its absolute address.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InstanceRegister<'a> {
+    /// The name of the register.
+    ///
+    /// Registers in nested register groups are prefixed by the group names,
+    /// for example `CH0.CTRLA`.
+    pub name: String,
+    /// The register description.
+    pub register: &'a Register,
+    /// The identifier of the address space the register lives in, for example `data`.
+    pub address_space: &'a str,
+    /// The absolute address of the register in the address space.
+    pub address: u32,
 }
 
 /// A group of registers.
 #[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct RegisterGroup {
     /// The name of the group.
     pub name: String,
     pub caption: String,
+    /// The number of bytes the group spans, if specified.
+    pub size: Option<u32>,
     /// The registers that make up the group.
     pub registers: Vec<Register>,
+    /// References to other register groups of the module nested inside this group.
+    ///
+    /// For example, XMEGA DMA controllers nest one `DMA_CH` group per channel.
+    pub register_groups: Vec<RegisterGroupRef>,
+    /// The register bitfield that selects between members, if the group is a union.
+    ///
+    /// Union members overlap each other, they are all found in `register_groups`.
+    pub union_tag: Option<UnionTag>,
+}
+
+/// A reference to a register group defined in a module.
+#[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RegisterGroupRef {
+    /// The name of the reference, for example `SINGLE`.
+    pub name: String,
+    /// The name of the referenced register group in the module, for example `TCA_SINGLE`.
+    pub name_in_module: String,
+    pub caption: String,
+    /// The offset of the referenced group relative to its container.
+    pub offset: u32,
+    /// The value of the union tag that selects this member, if inside a union.
+    pub union_tag_value: Option<u32>,
+    /// The address space the group is located in, if referenced from a peripheral instance.
+    pub address_space: Option<String>,
+}
+
+/// The bitfield used to select the active member of a union register group.
+///
+/// This is written as a dotted path, such as `TCA.SINGLE.CTRLD.SPLITM`: the module,
+/// the union member, the register and finally the bitfield.
+#[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UnionTag {
+    /// The dotted path to the bitfield.
+    pub path: String,
 }
 
 /// A group of values.
 #[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ValueGroup {
     pub name: String,
     pub caption: String,
@@ -147,6 +318,7 @@ pub struct ValueGroup {
 
 /// A values for a register/mask.
 #[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Value {
     pub name: String,
     pub caption: String,
@@ -155,6 +327,8 @@ pub struct Value {
 
 /// Specifies the mutability of a register.
 #[derive(Copy, Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum ReadWrite {
     /// The register is readable and writable.
     ReadAndWrite,
@@ -162,10 +336,16 @@ pub enum ReadWrite {
     ReadOnly,
     /// The register is write-only.
     WriteOnly,
+    /// The register can be neither read from nor written to.
+    ///
+    /// This is only used for debugger access, for registers where an access
+    /// would have side effects.
+    NoAccess,
 }
 
 /// An CPU or IO register.
 #[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Register {
     /// The name of the register, such as `TCCR0A`.
     pub name: String,
@@ -176,14 +356,36 @@ pub struct Register {
     /// The number of bytes that make up the bitfield.
     pub size: u32,
     pub mask: Option<u32>,
+    /// The value of the register after reset, if known.
+    pub initial_value: Option<u32>,
     /// The mutability of the register.
     pub rw: ReadWrite,
+    /// The mutability of the register when accessed by an on-chip debugger.
+    pub ocd_rw: ReadWrite,
     /// The bitfields supported by the register.
+    ///
+    /// These are present regardless of the mode the register is in.
+    pub bitfields: Vec<Bitfield>,
+    /// The modes the register can operate in.
+    ///
+    /// Registers such as the SPI `INTFLAGS` register on AVR8X parts have
+    /// a different set of bitfields depending on the peripheral mode.
+    pub modes: Vec<RegisterMode>,
+}
+
+/// A mode of a register, with the bitfields that apply in that mode.
+#[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RegisterMode {
+    /// The name of the mode, such as `BUFFERED`.
+    pub name: String,
+    /// The bitfields that are available in this mode.
     pub bitfields: Vec<Bitfield>,
 }
 
 /// A bitfield within a register.
 #[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Bitfield {
     /// The name of the bitfield, such as `U2X0` for the USART register `UCSR0A`.
     pub name: String,
@@ -196,17 +398,27 @@ pub struct Bitfield {
     pub mask: u32,
     /// The number of bytes that make up the bitfield.
     pub size: u32,
+    /// The mutability of the bitfield.
+    ///
+    /// A bitfield without its own access specifier inherits it from its register.
+    pub access: ReadWrite,
     /// reference into value_groups on the container
     pub values: Option<String>,
 }
 
 /// A signal that is exposed on the outside of the package.
 #[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Signal {
     /// The external pin name that exposes the signal.
     pub pad: String,
     pub group: Option<String>,
     pub index: Option<u8>,
+    /// The function the signal is routed for.
+    ///
+    /// This is `default` for the default pin of a signal, while alternate
+    /// routes selected through `PORTMUX` use names such as `USART0_ALT`.
+    pub function: Option<String>,
 }
 
 /// An AVR architecture (mcu family) name.
@@ -214,6 +426,8 @@ pub struct Signal {
 /// Architecture is a misnomer - 'mcu family' would make sense.
 /// Cores with the same instruction sets share an architecture name.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Architecture {
     Unknown,
 
@@ -237,6 +451,109 @@ pub enum Architecture {
     Tiny,
 }
 
+/// A revision of the AVR CPU core.
+///
+/// This is read from the `CORE_VERSION` parameter of the CPU.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "String", into = "String"))]
+pub enum CoreVersion {
+    /// Core version `V0`.
+    V0,
+    /// Core version `V0E`.
+    V0E,
+    /// Core version `V1`.
+    V1,
+    /// Core version `V2`, used by classic megaAVR and tinyAVR parts.
+    V2,
+    /// Core version `V2E`, used by classic megaAVR parts.
+    V2E,
+    /// Core version `V3`, used by AVR XMEGA parts.
+    V3,
+    /// Core version `V3X`, used by AVR XMEGA parts.
+    V3X,
+    /// Core version `V3XJ`, used by AVR XMEGA parts.
+    V3XJ,
+    /// Core version `V4`, used by AVR8X parts such as the tinyAVR 1-series.
+    V4,
+    /// Core version `AVR8L_0`, used by reduced core parts such as the ATtiny10.
+    Avr8L0,
+    /// A core version this crate does not know about.
+    Unknown(String),
+}
+
+/// The core architecture of a device, as named by the pack files.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "String", into = "String"))]
+pub enum CoreArchitecture {
+    /// The classic 8-bit AVR core.
+    Avr8,
+    /// The reduced 8-bit AVR core with 16 general purpose registers.
+    Avr8L,
+    /// The 8-bit AVR core used by AVR XMEGA parts.
+    Avr8Xmega,
+    /// The 8-bit AVR core used by newer parts, such as the tinyAVR 1-series.
+    Avr8X,
+    /// A core architecture this crate does not know about.
+    Unknown(String),
+}
+
+/// The product family of a device, as named by the pack files.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "String", into = "String"))]
+pub enum Family {
+    /// Classic megaAVR parts.
+    MegaAvr,
+    /// Classic tinyAVR parts.
+    TinyAvr,
+    /// AVR XMEGA parts.
+    Xmega,
+    /// AVR8X megaAVR parts, such as the megaAVR 0-series.
+    AvrMega,
+    /// AVR8X tinyAVR parts, such as the tinyAVR 0/1-series.
+    AvrTiny,
+    /// A family this crate does not know about.
+    Unknown(String),
+}
+
+/// A normalized summary of the memories of a microcontroller.
+///
+/// Packfiles describe memories inconsistently between families. For example,
+/// the internal SRAM is the `IRAM` segment of classic parts, but the
+/// `INTERNAL_SRAM` segment of AVR8X parts. Memories that do not exist are `None`.
+#[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MemoryMap {
+    /// The program memory.
+    pub flash: Option<MemoryRegion>,
+    /// The internal SRAM.
+    pub sram: Option<MemoryRegion>,
+    /// The EEPROM.
+    pub eeprom: Option<MemoryRegion>,
+    /// The user signature row.
+    pub user_signature: Option<MemoryRegion>,
+    /// The fuses.
+    pub fuses: Option<MemoryRegion>,
+}
+
+/// A memory of a microcontroller.
+#[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MemoryRegion {
+    /// The id of the address space the memory is in, such as `data`.
+    pub address_space: String,
+    /// The address of the first byte of the memory.
+    pub start: u32,
+    /// The size of the memory in bytes.
+    pub size: u32,
+    /// The size of a page in bytes, if the memory is written in pages.
+    ///
+    /// Packfiles that give a page size of 0 are read as having no pages.
+    pub page_size: Option<u32>,
+}
+
 /// A port, such as `PORTB`.
 #[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash)]
 pub struct Port<'a> {
@@ -252,23 +569,150 @@ impl Mcu {
         self.device.peripherals.iter().find(|p| p.name == name)
     }
 
+    /// Gets a peripheral instance by name, such as `USART1`.
+    pub fn instance(&self, name: &str) -> Option<&Instance> {
+        self.device.peripherals.iter().flat_map(|p| p.instances.iter()).find(|i| i.name == name)
+    }
+
+    /// Gets all registers of a peripheral instance, along with their absolute addresses.
+    ///
+    /// Registers in nested register groups are included. For union register groups,
+    /// the registers of every member are returned, and so may overlap.
+    ///
+    /// The module describing the registers is the module of the peripheral that
+    /// owns the instance. Register groups without an address space, which the
+    /// pack parser rejects but which hand-built models may contain, are placed
+    /// in the `data` address space.
+    pub fn instance_registers<'a>(&'a self, instance: &'a Instance) -> Vec<InstanceRegister<'a>> {
+        let mut registers = Vec::new();
+        let module = self
+            .device
+            .peripherals
+            .iter()
+            .find(|p| p.instance(&instance.name).is_some())
+            .and_then(|p| self.module(&p.name));
+
+        if let Some(module) = module {
+            for reference in instance.register_groups.iter() {
+                let address_space = reference.address_space.as_ref().map_or("data", |a| &a[..]);
+
+                if let Some(group) = module.resolve_register_group(reference) {
+                    module.collect_registers(
+                        group,
+                        reference.offset,
+                        "",
+                        address_space,
+                        &mut registers,
+                    );
+                }
+            }
+        }
+        registers
+    }
+
+    /// Gets the power-on value of every byte in the data address space.
+    ///
+    /// The map goes from data address to the reset value of the byte at that
+    /// address. Only registers with a known initial value are included. Multi-byte
+    /// registers are little endian. Where union members overlap, the first member
+    /// wins.
+    pub fn reset_state(&self) -> BTreeMap<u32, u8> {
+        let mut state = BTreeMap::new();
+        let instances = self.device.peripherals.iter().flat_map(|p| p.instances.iter());
+
+        for instance in instances {
+            for instance_register in self.instance_registers(instance) {
+                let initial_value = match instance_register.register.initial_value {
+                    Some(v) if instance_register.address_space == "data" => v,
+                    _ => continue,
+                };
+
+                for i in 0..instance_register.register.size {
+                    let byte = (initial_value >> (i * 8)) as u8;
+                    state.entry(instance_register.address + i).or_insert(byte);
+                }
+            }
+        }
+        state
+    }
+
+    /// Gets a summary of the memories.
+    pub fn memory(&self) -> MemoryMap {
+        let flash = self.device.address_space("prog").map(|space| {
+            let flash_segments = space.segments.iter().filter(|s| s.ty == "flash");
+            MemoryRegion {
+                page_size: flash_segments.filter_map(page_size).next(),
+                ..MemoryRegion::from_address_space(space)
+            }
+        });
+        let sram = self.device.address_space("data").and_then(|space| {
+            INTERNAL_SRAM_SEGMENTS
+                .iter()
+                .filter_map(|name| space.segments.iter().find(|s| s.ty == "ram" && s.name == *name))
+                .map(|segment| MemoryRegion::from_segment(space, segment))
+                .next()
+        });
+
+        MemoryMap {
+            flash,
+            sram,
+            eeprom: self.device.memory_region("eeprom"),
+            user_signature: self.device.memory_region("user_signatures"),
+            fuses: self.device.memory_region("fuses"),
+        }
+    }
+
     /// Gets a module by name.
     pub fn module(&self, name: &str) -> Option<&Module> {
         self.modules.iter().find(|p| p.name == name)
     }
 
+    /// Gets a device property by group and property name.
+    ///
+    /// ```nodoc
+    /// let signature0 = mcu.property("SIGNATURES", "SIGNATURE0").unwrap().as_int();
+    /// ```
+    pub fn property(&self, group: &str, name: &str) -> Option<&Property> {
+        self.device.property_group(group).and_then(|g| g.property(name))
+    }
+
+    /// Checks if the mcu can be programmed or debugged over an interface.
+    pub fn supports_interface(&self, interface: &ProgrammingInterface) -> bool {
+        self.device.interfaces.contains(interface)
+    }
+
+    /// Gets the revision of the CPU core.
+    ///
+    /// Returns `None` if the pack does not specify a core version.
+    pub fn core_version(&self) -> Option<CoreVersion> {
+        let instance_parameters =
+            self.device.peripherals.iter().flat_map(|p| p.instances.iter()).map(|i| &i.parameters);
+        let module_parameters = self.modules.iter().map(|m| &m.parameters);
+
+        instance_parameters
+            .chain(module_parameters)
+            .filter_map(|parameters| parameters.get("CORE_VERSION"))
+            .map(|version| CoreVersion::from_name(version))
+            .next()
+    }
+
+    /// Gets a pinout by name.
+    pub fn pinout(&self, name: &str) -> Option<&Pinout> {
+        self.pinouts.iter().find(|p| p.name == name)
+    }
+
     /// Gets an iterator over all register groups.
-    pub fn register_groups<'a>(&'a self) -> impl Iterator<Item = &'a RegisterGroup> {
+    pub fn register_groups(&self) -> impl Iterator<Item = &RegisterGroup> {
         self.modules.iter().flat_map(|m| m.register_groups.iter())
     }
 
     /// Gets an iterator over all registers.
-    pub fn registers<'a>(&'a self) -> impl Iterator<Item = &'a Register> {
+    pub fn registers(&self) -> impl Iterator<Item = &Register> {
         self.register_groups().flat_map(|rg| rg.registers.iter())
     }
 
     /// Gets a port by letter.
-    pub fn port(&self, letter: char) -> Port {
+    pub fn port(&self, letter: char) -> Port<'_> {
         let port_name = format!("PORT{}", letter);
         let instance = self
             .port_peripheral()
@@ -292,6 +736,146 @@ impl Mcu {
     }
 }
 
+impl Device {
+    /// Gets a property group by name.
+    pub fn property_group(&self, name: &str) -> Option<&PropertyGroup> {
+        self.property_groups.iter().find(|g| g.name == name)
+    }
+
+    /// Gets an address space by id, such as `prog` or `data`.
+    pub fn address_space(&self, id: &str) -> Option<&AddressSpace> {
+        self.address_spaces.iter().find(|space| space.id == id)
+    }
+
+    /// Finds a memory that is either a dedicated address space, as in classic
+    /// and XMEGA parts, or a segment of the data address space, as in AVR8X parts.
+    ///
+    /// The id of the address space is the same as the type of the segment.
+    fn memory_region(&self, ty: &str) -> Option<MemoryRegion> {
+        if let Some(space) = self.address_space(ty) {
+            let page_size = space.segments.iter().find(|s| s.ty == ty).and_then(page_size);
+            return Some(MemoryRegion { page_size, ..MemoryRegion::from_address_space(space) });
+        }
+
+        let data = self.address_space("data")?;
+        data.segments
+            .iter()
+            .find(|s| s.ty == ty)
+            .map(|segment| MemoryRegion::from_segment(data, segment))
+    }
+}
+
+impl MemoryMap {
+    /// Gets the first address of the internal SRAM, like `RAMSTART` in avr-libc.
+    pub fn ramstart(&self) -> Option<u32> {
+        self.sram.as_ref().map(|sram| sram.start)
+    }
+
+    /// Gets the last address of the internal SRAM, like `RAMEND` in avr-libc.
+    pub fn ramend(&self) -> Option<u32> {
+        self.sram.as_ref().and_then(MemoryRegion::end)
+    }
+
+    /// Gets the last byte offset of the flash, like `FLASHEND` in avr-libc.
+    pub fn flashend(&self) -> Option<u32> {
+        self.flash.as_ref().and_then(|flash| flash.size.checked_sub(1))
+    }
+
+    /// Gets the last byte offset of the EEPROM, like `E2END` in avr-libc.
+    ///
+    /// This is relative to the start of the EEPROM, even where the EEPROM
+    /// is mapped into the data address space.
+    pub fn e2end(&self) -> Option<u32> {
+        self.eeprom.as_ref().and_then(|eeprom| eeprom.size.checked_sub(1))
+    }
+
+    /// Gets the size of a flash page, like `SPM_PAGESIZE` in avr-libc.
+    pub fn spm_pagesize(&self) -> Option<u32> {
+        self.flash.as_ref().and_then(|flash| flash.page_size)
+    }
+}
+
+impl MemoryRegion {
+    fn from_address_space(space: &AddressSpace) -> Self {
+        MemoryRegion {
+            address_space: space.id.clone(),
+            start: space.start_address,
+            size: space.size,
+            page_size: None,
+        }
+    }
+
+    fn from_segment(space: &AddressSpace, segment: &MemorySegment) -> Self {
+        MemoryRegion {
+            address_space: space.id.clone(),
+            start: segment.start_address,
+            size: segment.size,
+            page_size: page_size(segment),
+        }
+    }
+
+    /// Gets the address of the last byte of the memory, if it is not empty.
+    pub fn end(&self) -> Option<u32> {
+        self.size.checked_sub(1).map(|last| self.start + last)
+    }
+}
+
+/// Gets the page size of a memory segment.
+///
+/// Packfiles give a page size of 0 for memories that are not written in
+/// pages, such as the flash of the ATtiny11.
+fn page_size(segment: &MemorySegment) -> Option<u32> {
+    segment.page_size.filter(|&size| size != 0)
+}
+
+impl PropertyGroup {
+    /// Gets a property by name.
+    pub fn property(&self, name: &str) -> Option<&Property> {
+        self.properties.iter().find(|p| p.name == name)
+    }
+}
+
+impl Property {
+    /// Interprets the value as an integer.
+    ///
+    /// Both decimal (`200`) and hexadecimal (`0x1e`) values are accepted.
+    pub fn as_int(&self) -> Option<u32> {
+        parse_int(&self.value)
+    }
+
+    /// Interprets the value as a whitespace-separated list of bytes.
+    ///
+    /// This is the format of properties such as `PpControlStack`.
+    pub fn as_bytes(&self) -> Option<Vec<u8>> {
+        self.value
+            .split_whitespace()
+            .map(|b| parse_int(b).and_then(|v| if v <= 0xff { Some(v as u8) } else { None }))
+            .collect()
+    }
+}
+
+impl Variant {
+    /// Resolves the pinout used by this variant.
+    ///
+    /// Returns `None` if the variant does not specify a pinout, or if
+    /// the mcu does not describe it.
+    pub fn pinout<'a>(&self, mcu: &'a Mcu) -> Option<&'a Pinout> {
+        self.pinout.as_ref().and_then(|name| mcu.pinout(name))
+    }
+}
+
+impl Pinout {
+    /// Gets the pin at a position.
+    pub fn pin(&self, position: &str) -> Option<&Pin> {
+        self.pins.iter().find(|p| p.position == position)
+    }
+
+    /// Gets an iterator over all pins connected to a pad.
+    pub fn pins_with_pad<'a>(&'a self, pad: &'a str) -> impl Iterator<Item = &'a Pin> {
+        self.pins.iter().filter(move |p| p.pad == pad)
+    }
+}
+
 impl Peripheral {
     /// Gets an instance by name.
     pub fn instance(&self, name: &str) -> Option<&Instance> {
@@ -299,18 +883,24 @@ impl Peripheral {
     }
 
     /// Gets an iterator over all signals that the peripheral uses.
-    pub fn signals<'a>(&'a self) -> impl Iterator<Item = &'a Signal> {
+    pub fn signals(&self) -> impl Iterator<Item = &Signal> {
         self.instances.iter().flat_map(|i| i.signals.iter())
     }
 
+    /// Gets an iterator over all signals that are routed for a function, such as `default`.
+    pub fn signals_for_function<'a>(
+        &'a self, function: &'a str,
+    ) -> impl Iterator<Item = &'a Signal> {
+        self.signals().filter(move |s| s.function.as_ref().map(String::as_ref) == Some(function))
+    }
+
     pub fn instance_signal_with_pad(&self, pad: &str) -> Option<(&Instance, &Signal)> {
         self.instance_signals_on_pad(pad).next()
     }
 
     /// Gets a tuple of `(instance, signal)` pairs that use a pad by its name.
     fn instance_signals_on_pad<'a>(
-        &'a self,
-        pad: &str,
+        &'a self, pad: &str,
     ) -> impl Iterator<Item = (&'a Instance, &'a Signal)> {
         let mut instance_signals = Vec::new();
 
@@ -332,9 +922,63 @@ impl Module {
     }
 
     /// Gets an iterator over all registers in the module.
-    pub fn registers<'a>(&'a self) -> impl Iterator<Item = &'a Register> {
+    pub fn registers(&self) -> impl Iterator<Item = &Register> {
         self.register_groups.iter().flat_map(|rg| rg.registers.iter())
     }
+
+    /// Gets a value group by name, such as the one a bitfield's `values` refers to.
+    pub fn value_group(&self, name: &str) -> Option<&ValueGroup> {
+        self.value_groups.iter().find(|vg| vg.name == name)
+    }
+
+    /// Resolves a register group reference to the group it refers to.
+    pub fn resolve_register_group(&self, reference: &RegisterGroupRef) -> Option<&RegisterGroup> {
+        self.register_group(&reference.name_in_module)
+    }
+
+    /// Collects the registers of a group and its nested groups, offset by a base address.
+    fn collect_registers<'a>(
+        &'a self, group: &'a RegisterGroup, base_address: u32, prefix: &str,
+        address_space: &'a str, registers: &mut Vec<InstanceRegister<'a>>,
+    ) {
+        for register in group.registers.iter() {
+            registers.push(InstanceRegister {
+                name: format!("{}{}", prefix, register.name),
+                register,
+                address_space,
+                address: base_address + register.offset,
+            });
+        }
+
+        for reference in group.register_groups.iter() {
+            if let Some(nested) = self.resolve_register_group(reference) {
+                self.collect_registers(
+                    nested,
+                    base_address + reference.offset,
+                    &format!("{}{}.", prefix, reference.name),
+                    address_space,
+                    registers,
+                );
+            }
+        }
+    }
+
+    /// Resolves the register and bitfield that select the active member of a union.
+    ///
+    /// Returns `None` if the group is not a union or the tag cannot be found.
+    pub fn union_tag_bitfield(&self, union: &RegisterGroup) -> Option<(&Register, &Bitfield)> {
+        let tag = union.union_tag.as_ref()?;
+        let member = union.register_group_ref(tag.member_name()?)?;
+        let register = self
+            .resolve_register_group(member)?
+            .registers
+            .iter()
+            .find(|r| Some(&r.name[..]) == tag.register_name())?;
+        let bitfield =
+            register.bitfields.iter().find(|b| Some(&b.name[..]) == tag.bitfield_name())?;
+
+        Some((register, bitfield))
+    }
 }
 
 impl Register {
@@ -347,15 +991,63 @@ impl Register {
 
         let mut result = self.clone();
 
-        match (result.mask, with.mask) {
-            (None, Some(v)) => result.mask = Some(v), // rhs is more specific
-            _ => (),
+        if let (None, Some(v)) = (result.mask, with.mask) {
+            result.mask = Some(v); // rhs is more specific
         }
 
         result
     }
 }
 
+impl RegisterGroup {
+    /// Checks if the register group is a union of other register groups.
+    pub fn is_union(&self) -> bool {
+        self.union_tag.is_some()
+    }
+
+    /// Gets a nested register group reference by name.
+    pub fn register_group_ref(&self, name: &str) -> Option<&RegisterGroupRef> {
+        self.register_groups.iter().find(|rg| rg.name == name)
+    }
+}
+
+impl UnionTag {
+    /// Gets the name of the union member that holds the tag register.
+    pub fn member_name(&self) -> Option<&str> {
+        self.segments().rev().nth(2)
+    }
+
+    /// Gets the name of the register that holds the tag.
+    pub fn register_name(&self) -> Option<&str> {
+        self.segments().rev().nth(1)
+    }
+
+    /// Gets the name of the bitfield that holds the tag.
+    pub fn bitfield_name(&self) -> Option<&str> {
+        self.segments().next_back()
+    }
+
+    fn segments(&self) -> ::std::str::Split<'_, char> {
+        self.path.split('.')
+    }
+}
+
+impl Register {
+    /// Gets a mode by name.
+    pub fn mode(&self, name: &str) -> Option<&RegisterMode> {
+        self.modes.iter().find(|m| m.name == name)
+    }
+
+    /// Gets an iterator over all bitfields that apply in a mode.
+    ///
+    /// This includes the bitfields common to all modes. If the register has
+    /// no mode with the given name, only the common bitfields are returned.
+    pub fn bitfields_for_mode<'a>(&'a self, mode: &str) -> impl Iterator<Item = &'a Bitfield> {
+        let mode_bitfields = self.mode(mode).map(|m| &m.bitfields[..]).unwrap_or(&[]);
+        self.bitfields.iter().chain(mode_bitfields.iter())
+    }
+}
+
 impl<'a> Port<'a> {
     /// Gets all associated registers.
     pub fn registers(&'a self) -> impl Iterator<Item = &'a Register> {
@@ -394,6 +1086,160 @@ impl<'a> Port<'a> {
     }
 }
 
+impl ReadWrite {
+    /// Checks if reads are allowed.
+    pub fn is_readable(&self) -> bool {
+        match self {
+            ReadWrite::ReadAndWrite | ReadWrite::ReadOnly => true,
+            ReadWrite::WriteOnly | ReadWrite::NoAccess => false,
+        }
+    }
+
+    /// Checks if writes are allowed.
+    pub fn is_writable(&self) -> bool {
+        match self {
+            ReadWrite::ReadAndWrite | ReadWrite::WriteOnly => true,
+            ReadWrite::ReadOnly | ReadWrite::NoAccess => false,
+        }
+    }
+}
+
+impl ProgrammingInterface {
+    /// Gets the interface from its pack file type name, such as `updi`.
+    pub fn from_name(name: &str) -> Self {
+        use ProgrammingInterface::*;
+
+        match name {
+            "isp" => Isp,
+            "hvpp" => Hvpp,
+            "hvsp" => Hvsp,
+            "dw" => DebugWire,
+            "megajtag" => MegaJtag,
+            "xmegajtag" => XmegaJtag,
+            "pdi" => Pdi,
+            "updi" => Updi,
+            "tpi" => Tpi,
+            name => Unknown(name.to_owned()),
+        }
+    }
+
+    /// Gets the pack file type name of the interface.
+    pub fn name(&self) -> &str {
+        use ProgrammingInterface::*;
+
+        match self {
+            Isp => "isp",
+            Hvpp => "hvpp",
+            Hvsp => "hvsp",
+            DebugWire => "dw",
+            MegaJtag => "megajtag",
+            XmegaJtag => "xmegajtag",
+            Pdi => "pdi",
+            Updi => "updi",
+            Tpi => "tpi",
+            Unknown(name) => name,
+        }
+    }
+}
+
+impl CoreVersion {
+    /// Gets the core version from its pack file name, such as `V2E`.
+    pub fn from_name(name: &str) -> Self {
+        use CoreVersion::*;
+
+        match name {
+            "V0" => V0,
+            "V0E" => V0E,
+            "V1" => V1,
+            "V2" => V2,
+            "V2E" => V2E,
+            "V3" => V3,
+            "V3X" => V3X,
+            "V3XJ" => V3XJ,
+            "V4" => V4,
+            "AVR8L_0" => Avr8L0,
+            name => Unknown(name.to_owned()),
+        }
+    }
+
+    /// Gets the pack file name of the core version.
+    pub fn name(&self) -> &str {
+        use CoreVersion::*;
+
+        match self {
+            V0 => "V0",
+            V0E => "V0E",
+            V1 => "V1",
+            V2 => "V2",
+            V2E => "V2E",
+            V3 => "V3",
+            V3X => "V3X",
+            V3XJ => "V3XJ",
+            V4 => "V4",
+            Avr8L0 => "AVR8L_0",
+            Unknown(name) => name,
+        }
+    }
+}
+
+impl CoreArchitecture {
+    /// Gets the core architecture from its pack file name, such as `AVR8X`.
+    pub fn from_name(name: &str) -> Self {
+        use CoreArchitecture::*;
+
+        match name {
+            "AVR8" => Avr8,
+            "AVR8L" => Avr8L,
+            "AVR8_XMEGA" => Avr8Xmega,
+            "AVR8X" => Avr8X,
+            name => Unknown(name.to_owned()),
+        }
+    }
+
+    /// Gets the pack file name of the core architecture.
+    pub fn name(&self) -> &str {
+        use CoreArchitecture::*;
+
+        match self {
+            Avr8 => "AVR8",
+            Avr8L => "AVR8L",
+            Avr8Xmega => "AVR8_XMEGA",
+            Avr8X => "AVR8X",
+            Unknown(name) => name,
+        }
+    }
+}
+
+impl Family {
+    /// Gets the family from its pack file name, such as `megaAVR`.
+    pub fn from_name(name: &str) -> Self {
+        use Family::*;
+
+        match name {
+            "megaAVR" => MegaAvr,
+            "tinyAVR" => TinyAvr,
+            "AVR XMEGA" => Xmega,
+            "AVR MEGA" => AvrMega,
+            "AVR TINY" => AvrTiny,
+            name => Unknown(name.to_owned()),
+        }
+    }
+
+    /// Gets the pack file name of the family.
+    pub fn name(&self) -> &str {
+        use Family::*;
+
+        match self {
+            MegaAvr => "megaAVR",
+            TinyAvr => "tinyAVR",
+            Xmega => "AVR XMEGA",
+            AvrMega => "AVR MEGA",
+            AvrTiny => "AVR TINY",
+            Unknown(name) => name,
+        }
+    }
+}
+
 impl Architecture {
     pub fn name(&self) -> &'static str {
         use Architecture::*;
@@ -421,3 +1267,63 @@ impl Architecture {
         }
     }
 }
+
+/// Implements conversions from and to the packfile names of an enum.
+macro_rules! impl_name_conversions {
+    ($($ty:ident),*) => {
+        $(
+            impl From<String> for $ty {
+                fn from(name: String) -> Self {
+                    $ty::from_name(&name)
+                }
+            }
+
+            impl From<$ty> for String {
+                fn from(value: $ty) -> Self {
+                    value.name().to_owned()
+                }
+            }
+        )*
+    };
+}
+
+impl_name_conversions!(ProgrammingInterface, CoreVersion, CoreArchitecture, Family);
+
+/// Parses a decimal or `0x`-prefixed hexadecimal integer.
+pub(crate) fn parse_int(value: &str) -> Option<u32> {
+    let value = value.trim();
+
+    if value.starts_with("0x") || value.starts_with("0X") {
+        u32::from_str_radix(&value[2..], 16).ok()
+    } else {
+        value.parse().ok()
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+    extern crate serde_json;
+
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "family-tiny")]
+    fn mcus_round_trip_through_serde() {
+        let mcu = ::microcontroller("attiny816");
+        let json = serde_json::to_string(mcu).unwrap();
+        assert_eq!(mcu, &serde_json::from_str::<Mcu>(&json).unwrap());
+    }
+
+    #[test]
+    fn enums_have_stable_representations() {
+        assert_eq!("\"AVR8X\"", serde_json::to_string(&CoreArchitecture::Avr8X).unwrap());
+        assert_eq!("\"AVR TINY\"", serde_json::to_string(&Family::AvrTiny).unwrap());
+        assert_eq!("\"updi\"", serde_json::to_string(&ProgrammingInterface::Updi).unwrap());
+        assert_eq!("\"avr25\"", serde_json::to_string(&Architecture::Avr25).unwrap());
+        assert_eq!("\"read_only\"", serde_json::to_string(&ReadWrite::ReadOnly).unwrap());
+        assert_eq!(
+            ProgrammingInterface::Unknown("foo".to_owned()),
+            serde_json::from_str("\"foo\"").unwrap()
+        );
+    }
+}
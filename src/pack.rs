@@ -1,177 +1,378 @@
 use super::*;
 
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io;
 use std::io::prelude::*;
 use std::path::Path;
+use std::str::FromStr;
 
 use xmltree::{Element, XMLNode};
 
-pub fn load(path: &Path) -> Result<Mcu, io::Error> {
-    let mut file = File::open(path)?;
-    let mut body = String::new();
-    file.read_to_string(&mut body)?;
+pub fn load(path: &Path) -> Result<Mcu, PackError> {
+    let read_file = || -> io::Result<String> {
+        let mut file = File::open(path)?;
+        let mut body = String::new();
+        file.read_to_string(&mut body)?;
+        Ok(body)
+    };
+    let body = read_file().map_err(|e| PackError::new(path, PackErrorKind::Io(e)))?;
+
+    self::parse(&body, path)
+}
+
+/// Parses the contents of a pack file.
+///
+/// The path is only used to describe errors.
+pub fn parse(body: &str, path: &Path) -> Result<Mcu, PackError> {
+    let root =
+        Element::parse(body.as_bytes()).map_err(|e| PackError::new(path, PackErrorKind::Xml(e)))?;
+
+    self::read_pack(&Node::root(&root)).map_err(|e| PackError { path: path.to_owned(), ..e })
+}
+
+/// An element in a packfile, along with its path from the root element.
+///
+/// Errors raised through a node record the path of the node, so that
+/// they can be tracked down in the packfile. The file path is filled in
+/// by `parse`.
+pub(crate) struct Node<'a> {
+    element: &'a Element,
+    path: String,
+}
+
+impl<'a> Node<'a> {
+    pub(crate) fn root(element: &'a Element) -> Self {
+        Node { element, path: String::new() }
+    }
+
+    pub(crate) fn error(&self, kind: PackErrorKind) -> PackError {
+        PackError { path: Default::default(), element_path: self.path.clone(), kind }
+    }
+
+    pub(crate) fn name(&self) -> &'a str {
+        &self.element.name
+    }
+
+    /// Gets an iterator over all child elements.
+    pub(crate) fn children(&self) -> impl Iterator<Item = Node<'a>> + '_ {
+        self.element
+            .children
+            .iter()
+            .filter_map(|node| if let XMLNode::Element(el) = node { Some(el) } else { None })
+            .map(move |element| {
+                let mut path = if self.path.is_empty() {
+                    element.name.clone()
+                } else {
+                    format!("{}/{}", self.path, element.name)
+                };
+                if let Some(name) = element.attributes.get("name") {
+                    path = format!("{}[{}]", path, name);
+                }
+                Node { element, path }
+            })
+    }
+
+    pub(crate) fn opt_child(&self, name: &str) -> Option<Node<'a>> {
+        self.children().find(|child| child.name() == name)
+    }
+
+    pub(crate) fn child(&self, name: &str) -> Result<Node<'a>, PackError> {
+        self.opt_child(name)
+            .ok_or_else(|| self.error(PackErrorKind::MissingElement(name.to_owned())))
+    }
+
+    /// Reads every child of an optional child element, such as all
+    /// `<pinout>` elements inside `<pinouts>`.
+    pub(crate) fn read_children_of<T, F>(&self, name: &str, read: F) -> Result<Vec<T>, PackError>
+    where
+        F: Fn(&Node<'a>) -> Result<T, PackError>,
+    {
+        match self.opt_child(name) {
+            Some(container) => container.children().map(|child| read(&child)).collect(),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Gets the text content of the element.
+    pub(crate) fn text(&self) -> String {
+        self.element.get_text().map(|text| text.into_owned()).unwrap_or_default()
+    }
+
+    pub(crate) fn opt_attr(&self, name: &str) -> Option<&'a String> {
+        self.element.attributes.get(name)
+    }
+
+    pub(crate) fn attr(&self, name: &str) -> Result<&'a String, PackError> {
+        self.opt_attr(name)
+            .ok_or_else(|| self.error(PackErrorKind::MissingAttribute(name.to_owned())))
+    }
+
+    /// Gets an optional attribute, or an empty string.
+    pub(crate) fn attr_or_empty(&self, name: &str) -> String {
+        self.opt_attr(name).cloned().unwrap_or_default()
+    }
+
+    pub(crate) fn malformed(&self, attribute: &str, value: &str) -> PackError {
+        self.error(PackErrorKind::MalformedAttribute {
+            attribute: attribute.to_owned(),
+            value: value.to_owned(),
+        })
+    }
+
+    pub(crate) fn parse<T: FromStr>(&self, name: &str) -> Result<T, PackError> {
+        let value = self.attr(name)?;
+        value.trim().parse().map_err(|_| self.malformed(name, value))
+    }
+
+    pub(crate) fn opt_parse<T: FromStr>(&self, name: &str) -> Result<Option<T>, PackError> {
+        match self.opt_attr(name) {
+            Some(_) => self.parse(name).map(Some),
+            None => Ok(None),
+        }
+    }
 
-    println!("loading pack '{}'", path.display());
-    let root = Element::parse(body.as_bytes()).unwrap();
+    /// Reads a decimal or hexadecimal integer attribute.
+    pub(crate) fn int(&self, name: &str) -> Result<u32, PackError> {
+        let value = self.attr(name)?;
+        parse_int(value).ok_or_else(|| self.malformed(name, value))
+    }
 
-    Ok(self::read_pack(&root))
+    pub(crate) fn opt_int(&self, name: &str) -> Result<Option<u32>, PackError> {
+        match self.opt_attr(name) {
+            Some(_) => self.int(name).map(Some),
+            None => Ok(None),
+        }
+    }
 }
 
-fn read_pack(root: &Element) -> Mcu {
-    let device_element = root.get_child("devices").unwrap().get_child("device").unwrap();
+fn read_pack(root: &Node) -> Result<Mcu, PackError> {
+    let device_element = root.child("devices")?.child("device")?;
 
-    let device = self::read_device(&device_element);
+    let device = self::read_device(&device_element)?;
     let variants = root
-        .get_child("variants")
-        .unwrap()
-        .children
-        .iter()
-        .filter_map(|node| if let XMLNode::Element(el) = node { Some(el) } else { None })
-        .map(self::read_variant)
-        .collect();
+        .child("variants")?
+        .children()
+        .map(|v| self::read_variant(&v))
+        .collect::<Result<_, _>>()?;
     let modules = root
-        .get_child("modules")
-        .unwrap()
-        .children
-        .iter()
-        .filter_map(|node| if let XMLNode::Element(el) = node { Some(el) } else { None })
-        .map(self::read_module);
+        .child("modules")?
+        .children()
+        .map(|m| self::read_module(&m))
+        .collect::<Result<_, _>>()?;
+    let pinouts = root.read_children_of("pinouts", self::read_pinout)?;
 
     // Not all desired information is available in pack files.
     // Grab the remaining bits from a lookup table.
     let extra_info = super::extra_info::lookup(&device.name);
 
-    Mcu {
-        device: device,
-        variants: variants,
-        modules: modules.collect(),
-        architecture: extra_info.arch,
+    let mut mcu = Mcu {
+        device,
+        variants,
+        modules,
+        pinouts,
+        architecture: Architecture::Unknown,
         c_preprocessor_name: extra_info.c_preprocessor_name,
-    }
+    };
+    mcu.architecture =
+        extra_info.arch.unwrap_or_else(|| super::extra_info::infer_architecture(&mcu));
+    Ok(mcu)
 }
 
-fn read_device(device: &Element) -> Device {
-    let device_name = device.attributes.get("name").unwrap().clone();
+fn read_device(device: &Node) -> Result<Device, PackError> {
+    let device_name = device.attr("name")?.clone();
+    let architecture = CoreArchitecture::from_name(device.attr("architecture")?);
+    let family = Family::from_name(device.attr("family")?);
 
     let peripherals = device
-        .get_child("peripherals")
-        .unwrap()
-        .children
-        .iter()
-        .filter_map(|node| if let XMLNode::Element(el) = node { Some(el) } else { None })
-        .map(self::read_peripheral)
-        .collect();
+        .child("peripherals")?
+        .children()
+        .map(|p| self::read_peripheral(&p))
+        .collect::<Result<_, _>>()?;
 
     let address_spaces = device
-        .get_child("address-spaces")
-        .unwrap()
-        .children
-        .iter()
-        .filter_map(|node| if let XMLNode::Element(el) = node { Some(el) } else { None })
-        .map(self::read_address_space)
-        .collect();
+        .child("address-spaces")?
+        .children()
+        .map(|a| self::read_address_space(&a))
+        .collect::<Result<_, _>>()?;
 
     let interrupts = device
-        .get_child("interrupts")
-        .unwrap()
-        .children
-        .iter()
-        .filter_map(|node| if let XMLNode::Element(el) = node { Some(el) } else { None })
-        .map(self::read_interrupt)
-        .collect();
-
-    Device { name: device_name, address_spaces: address_spaces, peripherals, interrupts }
+        .child("interrupts")?
+        .children()
+        .map(|i| self::read_interrupt(&i))
+        .collect::<Result<_, _>>()?;
+
+    let property_groups = device.read_children_of("property-groups", self::read_property_group)?;
+
+    let interfaces = device.read_children_of("interfaces", |interface| {
+        interface.attr("type").map(|ty| ProgrammingInterface::from_name(ty))
+    })?;
+
+    Ok(Device {
+        name: device_name,
+        architecture,
+        family,
+        address_spaces,
+        peripherals,
+        interrupts,
+        property_groups,
+        interfaces,
+        parameters: self::read_parameters(device)?,
+    })
 }
 
-fn read_interrupt(interrupt: &Element) -> Interrupt {
-    let index: u32 = read_int(interrupt.attributes.get("index")).clone();
-    Interrupt {
-        name: interrupt.attributes.get("name").unwrap_or(&format!("INT{}", index)).clone(),
-        caption: interrupt.attributes.get("caption").unwrap_or(&format!("INT{}", index)).clone(),
+fn read_interrupt(interrupt: &Node) -> Result<Interrupt, PackError> {
+    let index: u32 = interrupt.int("index")?;
+    Ok(Interrupt {
+        name: interrupt.opt_attr("name").cloned().unwrap_or_else(|| format!("INT{}", index)),
+        caption: interrupt.opt_attr("caption").cloned().unwrap_or_else(|| format!("INT{}", index)),
         index,
-    }
+    })
 }
 
-fn read_peripheral(module: &Element) -> Peripheral {
-    let name = module.attributes.get("name").unwrap().clone();
+/// Reads a property group.
+///
+/// This looks like
+///
+/// ```xml
+/// <property-group name="SIGNATURES">
+///   <property name="SIGNATURE0" value="0x1e"/>
+///   <property name="SIGNATURE1" value="0x95"/>
+/// </property-group>
+/// ```
+fn read_property_group(property_group: &Node) -> Result<PropertyGroup, PackError> {
+    let properties = property_group
+        .children()
+        .map(|property| {
+            Ok(Property {
+                name: property.attr("name")?.clone(),
+                value: property.attr("value")?.clone(),
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(PropertyGroup { name: property_group.attr("name")?.clone(), properties })
+}
+
+fn read_peripheral(module: &Node) -> Result<Peripheral, PackError> {
+    let name = module.attr("name")?.clone();
     let mut instances = Vec::new();
 
-    for child in module.children.iter().filter_map(|node| {
-        if let XMLNode::Element(el) = node {
-            Some(el)
-        } else {
-            None
-        }
-    }) {
-        match &child.name[..] {
-            "instance" => instances.push(read_instance(child)),
-            // Unimplemented tags.
-            _ => (),
+    for child in module.children() {
+        // Other tags are unimplemented.
+        if child.name() == "instance" {
+            instances.push(read_instance(&child)?);
         }
     }
 
-    Peripheral { name, instances }
+    Ok(Peripheral { name, instances })
 }
 
-fn read_module(module: &Element) -> Module {
-    let module_name = module.attributes.get("name").unwrap().clone();
+fn read_module(module: &Node) -> Result<Module, PackError> {
+    let module_name = module.attr("name")?.clone();
     let mut register_groups = Vec::new();
     let mut value_groups = Vec::new();
 
-    for child in module.children.iter().filter_map(|node| {
-        if let XMLNode::Element(el) = node {
-            Some(el)
-        } else {
-            None
-        }
-    }) {
-        match &child.name[..] {
-            "register-group" => register_groups.push(read_register_group(child)),
-            "value-group" => value_groups.push(read_value_group(child)),
+    for child in module.children() {
+        match child.name() {
+            "register-group" => register_groups.push(read_register_group(&child)?),
+            "value-group" => value_groups.push(read_value_group(&child)?),
             // Unimplemented tags.
             _ => (),
         }
     }
 
-    Module { name: module_name, register_groups: register_groups, value_groups }
+    Ok(Module {
+        name: module_name,
+        register_groups,
+        value_groups,
+        parameters: self::read_parameters(module)?,
+    })
 }
 
-fn read_variant(variant: &Element) -> Variant {
-    Variant {
-        name: variant.attributes.get("ordercode").unwrap().clone(),
-        temperature_min: variant.attributes.get("tempmin").unwrap().parse().unwrap(),
-        temperature_max: variant.attributes.get("tempmax").unwrap().parse().unwrap(),
-        voltage_min: variant.attributes.get("vccmin").unwrap().parse().unwrap(),
-        voltage_max: variant.attributes.get("vccmax").unwrap().parse().unwrap(),
-        package: variant.attributes.get("package").unwrap().clone(),
-        pinout: variant.attributes.get("pinout").map(|p| p.clone()),
-        speed_max_hz: variant.attributes.get("speedmax").unwrap().parse().unwrap(),
-    }
+fn read_variant(variant: &Node) -> Result<Variant, PackError> {
+    Ok(Variant {
+        name: variant.attr("ordercode")?.clone(),
+        temperature_min: variant.parse("tempmin")?,
+        temperature_max: variant.parse("tempmax")?,
+        voltage_min: variant.parse("vccmin")?,
+        voltage_max: variant.parse("vccmax")?,
+        package: variant.attr("package")?.clone(),
+        pinout: variant.opt_attr("pinout").cloned(),
+        speed_max_hz: variant.parse("speedmax")?,
+    })
 }
 
-fn read_instance(instance: &Element) -> Instance {
-    let instance_name = instance.attributes.get("name").unwrap().clone();
+/// Reads a pinout.
+///
+/// This looks like
+///
+/// ```xml
+/// <pinout name="TQFP32" caption="TQFP32">
+///   <pin position="1" pad="PD3"/>
+///   <pin position="2" pad="PD4"/>
+/// </pinout>
+/// ```
+fn read_pinout(pinout: &Node) -> Result<Pinout, PackError> {
+    let name = pinout.attr("name")?.clone();
+    let pins = pinout.children().map(|pin| read_pin(&pin)).collect::<Result<_, _>>()?;
 
-    let signals = match instance.get_child("signals") {
-        Some(signals) => signals
-            .children
-            .iter()
-            .filter_map(|node| if let XMLNode::Element(el) = node { Some(el) } else { None })
-            .map(read_signal)
-            .collect(),
-        None => Vec::new(),
-    };
+    Ok(Pinout { caption: pinout.attr_or_empty("caption"), name, pins })
+}
 
-    Instance { name: instance_name, signals: signals }
+fn read_pin(pin: &Node) -> Result<Pin, PackError> {
+    Ok(Pin { position: pin.attr("position")?.clone(), pad: pin.attr("pad")?.clone() })
 }
 
-fn read_signal(signal: &Element) -> Signal {
-    Signal {
-        pad: signal.attributes.get("pad").unwrap().clone(),
-        group: signal.attributes.get("group").map(|p| p.clone()),
-        index: signal.attributes.get("index").map(|i| i.parse().unwrap()),
-    }
+fn read_instance(instance: &Node) -> Result<Instance, PackError> {
+    let instance_name = instance.attr("name")?.clone();
+
+    let register_groups = instance
+        .children()
+        .filter(|child| child.name() == "register-group")
+        .map(|child| {
+            // Unlike nested references, instances place their register groups
+            // into an address space.
+            child.attr("address-space")?;
+            self::read_register_group_ref(&child)
+        })
+        .collect::<Result<_, _>>()?;
+
+    let signals = instance.read_children_of("signals", read_signal)?;
+
+    Ok(Instance {
+        name: instance_name,
+        caption: instance.attr_or_empty("caption"),
+        register_groups,
+        signals,
+        parameters: self::read_parameters(instance)?,
+    })
+}
+
+/// Reads the parameters of an element.
+///
+/// This looks like
+///
+/// ```xml
+/// <parameters>
+///   <param name="CORE_VERSION" value="V2E"/>
+/// </parameters>
+/// ```
+fn read_parameters(element: &Node) -> Result<BTreeMap<String, String>, PackError> {
+    let parameters = element.read_children_of("parameters", |param| {
+        Ok((param.attr("name")?.clone(), param.attr("value")?.clone()))
+    })?;
+
+    Ok(parameters.into_iter().collect())
+}
+
+fn read_signal(signal: &Node) -> Result<Signal, PackError> {
+    Ok(Signal {
+        pad: signal.attr("pad")?.clone(),
+        group: signal.opt_attr("group").cloned(),
+        index: signal.opt_parse("index")?,
+        function: signal.opt_attr("function").cloned(),
+    })
 }
 
 /// Reads a register group.
@@ -183,24 +384,64 @@ fn read_signal(signal: &Element) -> Signal {
 ///   <register caption="EEPROM Address Register  Bytes" name="EEAR" offset="0x41" size="2" mask="0x01FF"/>
 ///   <register caption="EEPROM Data Register" name="EEDR" offset="0x40" size="1" mask="0xFF"/>
 /// </register-group>
-fn read_register_group(register_group: &Element) -> RegisterGroup {
-    let (name, caption) = (
-        register_group.attributes.get("name").unwrap(),
-        register_group.attributes.get("caption").unwrap(),
-    );
-    let registers = register_group
-        .children
-        .iter()
-        .filter_map(|node| if let XMLNode::Element(el) = node { Some(el) } else { None })
-        .filter_map(|child| match &child.name[..] {
-            "register" => Some(self::read_register(child)),
-            // FIXME: leave this out for now, ATtiny816 has nested register-group
-            // _ => panic!("unknown register-group child: '{}'", child.name),
-            _ => None,
-        })
-        .collect();
+/// ```
+///
+/// Register groups can also be unions of other register groups in the module.
+///
+/// ```xml
+/// <register-group caption="16-bit Timer/Counter Type A" class="union" name="TCA" size="0x40" union-tag="TCA.SINGLE.CTRLD.SPLITM">
+///   <register-group name="SINGLE" name-in-module="TCA_SINGLE" offset="0" union-tag-value="0"/>
+///   <register-group name="SPLIT" name-in-module="TCA_SPLIT" offset="0" union-tag-value="1"/>
+/// </register-group>
+/// ```
+fn read_register_group(register_group: &Node) -> Result<RegisterGroup, PackError> {
+    let (name, caption) = (register_group.attr("name")?, register_group.attr("caption")?);
+    let mut registers = Vec::new();
+    let mut register_groups = Vec::new();
+
+    for child in register_group.children() {
+        match child.name() {
+            "register" => registers.push(self::read_register(&child)?),
+            "register-group" => register_groups.push(self::read_register_group_ref(&child)?),
+            name => {
+                return Err(register_group.error(PackErrorKind::UnexpectedElement(name.to_owned())))
+            }
+        }
+    }
+
+    let union_tag = match register_group.opt_attr("class").map(String::as_ref) {
+        Some("union") => Some(UnionTag { path: register_group.attr("union-tag")?.clone() }),
+        _ => None,
+    };
+
+    Ok(RegisterGroup {
+        name: name.clone(),
+        caption: caption.clone(),
+        size: register_group.opt_int("size")?,
+        registers,
+        register_groups,
+        union_tag,
+    })
+}
 
-    RegisterGroup { name: name.clone(), caption: caption.clone(), registers: registers }
+/// Reads a reference to a register group.
+///
+/// This looks like
+///
+/// ```xml
+/// <register-group caption="DMA Channel 0" name="CH0" offset="0x10" name-in-module="DMA_CH"/>
+/// <register-group name="SINGLE" name-in-module="TCA_SINGLE" offset="0" union-tag-value="0"/>
+/// <register-group address-space="data" name="PORTA" name-in-module="PORT" offset="0x0400"/>
+/// ```
+fn read_register_group_ref(register_group: &Node) -> Result<RegisterGroupRef, PackError> {
+    Ok(RegisterGroupRef {
+        name: register_group.attr("name")?.clone(),
+        name_in_module: register_group.attr("name-in-module")?.clone(),
+        caption: register_group.attr_or_empty("caption"),
+        offset: register_group.int("offset")?,
+        union_tag_value: register_group.opt_int("union-tag-value")?,
+        address_space: register_group.opt_attr("address-space").cloned(),
+    })
 }
 
 /// Reads a value group.
@@ -213,22 +454,17 @@ fn read_register_group(register_group: &Element) -> RegisterGroup {
 ///        <value caption="Running, No Prescaling" name="VAL_0x01" value="0x01"/>
 ///      </value-group>
 /// ```
-fn read_value_group(value_group: &Element) -> ValueGroup {
-    let (name, caption) = (
-        value_group.attributes.get("name").unwrap(),
-        value_group.attributes.get("caption").unwrap(),
-    );
+fn read_value_group(value_group: &Node) -> Result<ValueGroup, PackError> {
+    let (name, caption) = (value_group.attr("name")?, value_group.attr("caption")?);
     let values = value_group
-        .children
-        .iter()
-        .filter_map(|node| if let XMLNode::Element(el) = node { Some(el) } else { None })
-        .filter_map(|child| match &child.name[..] {
-            "value" => Some(self::read_value(child)),
-            _ => panic!("unknown value-group child: '{}'", child.name),
+        .children()
+        .map(|child| match child.name() {
+            "value" => self::read_value(&child),
+            name => Err(value_group.error(PackErrorKind::UnexpectedElement(name.to_owned()))),
         })
-        .collect();
+        .collect::<Result<_, _>>()?;
 
-    ValueGroup { name: name.clone(), caption: caption.clone(), values }
+    Ok(ValueGroup { name: name.clone(), caption: caption.clone(), values })
 }
 
 /// Reads a value.
@@ -238,12 +474,12 @@ fn read_value_group(value_group: &Element) -> ValueGroup {
 /// ```xml
 /// <value caption="Running, CLK/256" name="VAL_0x04" value="0x04"/>
 /// ```
-fn read_value(value: &Element) -> Value {
-    Value {
-        name: value.attributes.get("name").unwrap().clone(),
-        caption: value.attributes.get("caption").unwrap().clone(),
-        value: read_int(value.attributes.get("value")).clone(),
-    }
+fn read_value(value: &Node) -> Result<Value, PackError> {
+    Ok(Value {
+        name: value.attr("name")?.clone(),
+        caption: value.attr("caption")?.clone(),
+        value: value.int("value")?,
+    })
 }
 
 /// Reads a register.
@@ -252,34 +488,59 @@ fn read_value(value: &Element) -> Value {
 ///
 /// ```xml
 /// <register caption="EEPROM Address Register  Bytes" name="EEAR" offset="0x41" size="2" mask="0x01FF" ocd-rw=""/>
+/// <register caption="ADC Accumulator Result" name="RES" offset="0x10" rw="R" size="2"/>
 /// ```
-fn read_register(register: &Element) -> Register {
-    let byte_count = register.attributes.get("size").unwrap().parse().unwrap();
-    let rw = match register.attributes.get("ocd-rw").map(String::as_ref) {
-        Some("R") => ReadWrite::ReadOnly,
-        Some("W") => ReadWrite::WriteOnly,
-        _ => ReadWrite::ReadAndWrite,
-    };
-
-    let bitfields = register
-        .children
-        .iter()
-        .filter_map(|node| if let XMLNode::Element(el) = node { Some(el) } else { None })
-        .filter_map(|child| match &child.name[..] {
-            "bitfield" => Some(self::read_bitfield(child, byte_count)),
-            _ => None,
-        })
-        .collect();
+fn read_register(register: &Node) -> Result<Register, PackError> {
+    let byte_count = register.parse("size")?;
+    let rw = read_rw(register, "rw")?.unwrap_or(ReadWrite::ReadAndWrite);
+    let ocd_rw = read_rw(register, "ocd-rw")?.unwrap_or(ReadWrite::ReadAndWrite);
+
+    let mut bitfields = Vec::new();
+    let mut modes = Vec::new();
+
+    for child in register.children() {
+        match child.name() {
+            "bitfield" => bitfields.push(self::read_bitfield(&child, byte_count, rw)?),
+            "mode" => modes.push(self::read_register_mode(&child, byte_count, rw)?),
+            // Unimplemented tags.
+            _ => (),
+        }
+    }
 
-    Register {
-        name: register.attributes.get("name").unwrap().clone(),
-        caption: register.attributes.get("caption").unwrap().clone(),
-        offset: read_int(register.attributes.get("offset")).clone(),
-        mask: read_opt_int(register.attributes.get("mask")).clone(),
+    Ok(Register {
+        name: register.attr("name")?.clone(),
+        caption: register.attr("caption")?.clone(),
+        offset: register.int("offset")?,
+        mask: register.opt_int("mask")?,
+        initial_value: register.opt_int("initval")?,
         size: byte_count,
         bitfields,
+        modes,
         rw,
-    }
+        ocd_rw,
+    })
+}
+
+/// Reads a register mode.
+///
+/// This looks like
+///
+/// ```xml
+/// <mode name="DEFAULT">
+///   <bitfield caption="Interrupt Flag" mask="0x80" name="IF" rw="RW"/>
+///   <bitfield caption="Write Collision" mask="0x40" name="WRCOL" rw="RW"/>
+/// </mode>
+/// ```
+fn read_register_mode(
+    mode: &Node, byte_count: u32, register_rw: ReadWrite,
+) -> Result<RegisterMode, PackError> {
+    let bitfields = mode
+        .children()
+        .filter(|child| child.name() == "bitfield")
+        .map(|child| self::read_bitfield(&child, byte_count, register_rw))
+        .collect::<Result<_, _>>()?;
+
+    Ok(RegisterMode { name: mode.attr("name")?.clone(), bitfields })
 }
 
 /// Reads a bitfield.
@@ -289,14 +550,36 @@ fn read_register(register: &Element) -> Register {
 /// ```xml
 /// <bitfield caption="Power Reduction Serial Peripheral Interface" mask="0x04" name="PRSPI"/>
 /// <bitfield caption="Prescaler source of Timer/Counter 3" mask="0x07" name="CS3" values="CLK_SEL_3BIT_EXT"/>
+/// <bitfield caption="Voltage level monitor status" mask="0x1" name="VLMS" rw="R"/>
 /// ```
-fn read_bitfield(bitfield: &Element, byte_count: u32) -> Bitfield {
-    Bitfield {
-        name: bitfield.attributes.get("name").expect("bitfield name").clone(),
-        caption: bitfield.attributes.get("caption").unwrap_or(&"".to_owned()).clone(),
-        mask: read_int(bitfield.attributes.get("mask")).clone(),
-        values: bitfield.attributes.get("values").map(String::clone),
+fn read_bitfield(
+    bitfield: &Node, byte_count: u32, register_rw: ReadWrite,
+) -> Result<Bitfield, PackError> {
+    Ok(Bitfield {
+        name: bitfield.attr("name")?.clone(),
+        caption: bitfield.attr_or_empty("caption"),
+        mask: bitfield.int("mask")?,
+        values: bitfield.opt_attr("values").cloned(),
         size: byte_count,
+        access: read_rw(bitfield, "rw")?.unwrap_or(register_rw),
+    })
+}
+
+/// Reads an access specifier, such as `rw="R"` or `ocd-rw=""`.
+///
+/// Only `ocd-rw` may be empty, meaning that the debugger cannot access the
+/// register.
+fn read_rw(element: &Node, attribute: &str) -> Result<Option<ReadWrite>, PackError> {
+    let value = match element.opt_attr(attribute) {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    match &value[..] {
+        "R" => Ok(Some(ReadWrite::ReadOnly)),
+        "W" => Ok(Some(ReadWrite::WriteOnly)),
+        "RW" => Ok(Some(ReadWrite::ReadAndWrite)),
+        "" if attribute == "ocd-rw" => Ok(Some(ReadWrite::NoAccess)),
+        _ => Err(element.malformed(attribute, value)),
     }
 }
 
@@ -309,24 +592,22 @@ fn read_bitfield(bitfield: &Element, byte_count: u32) -> Bitfield {
 ///   <memory-segment start="0" size="3" type="signatures" rw="R" exec="0" name="SIGNATURES"/>
 /// </address-space>
 /// ```
-fn read_address_space(address_space: &Element) -> AddressSpace {
-    let id = address_space.attributes.get("id").unwrap().clone();
-    let start_address = read_int(address_space.attributes.get("start"));
-    let size = read_int(address_space.attributes.get("size"));
+fn read_address_space(address_space: &Node) -> Result<AddressSpace, PackError> {
+    let id = address_space.attr("id")?.clone();
+    let start_address = address_space.int("start")?;
+    let size = address_space.int("size")?;
     let segments = address_space
-        .children
-        .iter()
-        .filter_map(|node| if let XMLNode::Element(el) = node { Some(el) } else { None })
-        .map(read_memory_segment)
-        .collect();
-
-    AddressSpace {
-        id: id,
-        name: address_space.attributes.get("name").unwrap().clone(),
-        start_address: start_address,
-        size: size,
-        segments: segments,
-    }
+        .children()
+        .map(|segment| read_memory_segment(&segment))
+        .collect::<Result<_, _>>()?;
+
+    Ok(AddressSpace {
+        id,
+        name: address_space.attr("name")?.clone(),
+        start_address,
+        size,
+        segments,
+    })
 }
 
 /// Reads a memory segment.
@@ -334,38 +615,327 @@ fn read_address_space(address_space: &Element) -> AddressSpace {
 /// ```xml
 /// <memory-segment start="0" size="3" type="signatures" rw="R" exec="0" name="SIGNATURES"/>
 /// ```
-fn read_memory_segment(memory_segment: &Element) -> MemorySegment {
-    let default_perms = "".to_owned();
-
-    let start_address = read_int(memory_segment.attributes.get("start"));
-    let size = read_int(memory_segment.attributes.get("size"));
-    let ty = memory_segment.attributes.get("type").unwrap().clone();
-    let rw = memory_segment.attributes.get("rw").unwrap_or(&default_perms);
-    let exec = memory_segment.attributes.get("exec").unwrap_or(&default_perms);
-    let name = memory_segment.attributes.get("name").unwrap().clone();
-    let page_size = memory_segment.attributes.get("pagesize").map(|p| read_int(Some(p)));
-
-    let readable = rw.contains("r") || rw.contains("R");
-    let writable = rw.contains("w") || rw.contains("W");
+fn read_memory_segment(memory_segment: &Node) -> Result<MemorySegment, PackError> {
+    let start_address = memory_segment.int("start")?;
+    let size = memory_segment.int("size")?;
+    let ty = memory_segment.attr("type")?.clone();
+    let rw = memory_segment.attr_or_empty("rw");
+    let exec = memory_segment.attr_or_empty("exec");
+    let name = memory_segment.attr("name")?.clone();
+    let page_size = memory_segment.opt_int("pagesize")?;
+
+    let readable = rw.contains('r') || rw.contains('R');
+    let writable = rw.contains('w') || rw.contains('W');
     let executable = exec == "1";
 
-    MemorySegment { start_address, size, ty, name, readable, writable, executable, page_size }
+    Ok(MemorySegment { start_address, size, ty, name, readable, writable, executable, page_size })
 }
 
-fn read_int(value: Option<&String>) -> u32 {
-    let value = value.unwrap();
+#[cfg(test)]
+mod test {
+    use super::*;
 
-    match value.starts_with("0x") {
-        true => read_hex(Some(value)),
-        false => value.parse().unwrap(),
+    fn load_pack(collection: &str, name: &str) -> Mcu {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("packs")
+            .join(collection)
+            .join(format!("{}.atdf", name));
+        load(&path).unwrap()
     }
-}
 
-fn read_opt_int(value: Option<&String>) -> Option<u32> {
-    value.map(|v| if v.starts_with("0x") { read_hex(Some(v)) } else { v.parse().unwrap() })
-}
+    #[test]
+    fn malformed_packs_are_errors() {
+        let pack = r#"<avr-tools-device-file>
+              <variants/>
+              <devices>
+                <device name="ATfoo" architecture="AVR8" family="megaAVR">
+                  <peripherals/>
+                  <address-spaces>
+                    <address-space id="data" name="data" start="0x0000" size="lots"/>
+                  </address-spaces>
+                </device>
+              </devices>
+            </avr-tools-device-file>"#;
+        let error = parse(pack, Path::new("ATfoo.atdf")).unwrap_err();
+
+        assert_eq!(Path::new("ATfoo.atdf"), error.path);
+        assert_eq!("devices/device[ATfoo]/address-spaces/address-space[data]", error.element_path);
+        match error.kind {
+            PackErrorKind::MalformedAttribute { ref attribute, ref value } => {
+                assert_eq!(("size", "lots"), (&attribute[..], &value[..]))
+            }
+            ref kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn instance_register_groups_need_an_address_space() {
+        let pack = r#"<avr-tools-device-file>
+              <variants/>
+              <devices>
+                <device name="ATfoo" architecture="AVR8" family="megaAVR">
+                  <address-spaces/>
+                  <peripherals>
+                    <module name="PORT">
+                      <instance name="PORTB">
+                        <register-group name="PORTB" name-in-module="PORTB" offset="0x00"/>
+                      </instance>
+                    </module>
+                  </peripherals>
+                </device>
+              </devices>
+            </avr-tools-device-file>"#;
+        let error = parse(pack, Path::new("ATfoo.atdf")).unwrap_err();
+
+        assert_eq!(
+            "devices/device[ATfoo]/peripherals/module[PORT]/instance[PORTB]/register-group[PORTB]",
+            error.element_path
+        );
+        match error.kind {
+            PackErrorKind::MissingAttribute(ref attribute) => {
+                assert_eq!("address-space", attribute)
+            }
+            ref kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn unknown_access_specifiers_are_errors() {
+        let pack = r#"<avr-tools-device-file>
+              <variants/>
+              <devices>
+                <device name="ATfoo" architecture="AVR8" family="megaAVR">
+                  <address-spaces/>
+                  <peripherals/>
+                  <interrupts/>
+                </device>
+              </devices>
+              <modules>
+                <module name="ADC">
+                  <register-group caption="ADC" name="ADC">
+                    <register caption="ADC Data" name="ADC" offset="0x78" size="2" rw=""/>
+                  </register-group>
+                </module>
+              </modules>
+            </avr-tools-device-file>"#;
+        let error = parse(pack, Path::new("ATfoo.atdf")).unwrap_err();
+
+        assert_eq!("modules/module[ADC]/register-group[ADC]/register[ADC]", error.element_path);
+        match error.kind {
+            PackErrorKind::MalformedAttribute { ref attribute, ref value } => {
+                assert_eq!(("rw", ""), (&attribute[..], &value[..]))
+            }
+            ref kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn atmega328p_variants_resolve_pinouts() {
+        let mcu = load_pack("atmega", "ATmega328P");
+        let variant = mcu.variants.iter().find(|v| v.name == "ATmega328P-PU").unwrap();
+        let pinout = variant.pinout(&mcu).expect("PDIP28 pinout");
+
+        assert_eq!("PDIP28", pinout.name);
+        assert_eq!(28, pinout.pins.len());
+        assert_eq!("PB5", pinout.pin("19").unwrap().pad);
+    }
+
+    #[test]
+    fn atmega328p_properties_are_typed() {
+        let mcu = load_pack("atmega", "ATmega328P");
+
+        assert_eq!(Some(0x95), mcu.property("SIGNATURES", "SIGNATURE1").unwrap().as_int());
+        assert_eq!(
+            Some(200),
+            mcu.property("ISP_INTERFACE", "IspEnterProgMode_timeout").unwrap().as_int()
+        );
+
+        let control_stack = mcu.property("PP_INTERFACE", "PpControlStack").unwrap();
+        let bytes = control_stack.as_bytes().unwrap();
+        assert_eq!(32, bytes.len());
+        assert_eq!(&[0x0E, 0x1E, 0x0F], &bytes[..3]);
+        assert_eq!(None, control_stack.as_int());
+    }
+
+    #[test]
+    fn interfaces_are_read() {
+        let atmega328p = load_pack("atmega", "ATmega328P");
+        assert!(atmega328p.supports_interface(&ProgrammingInterface::Isp));
+        assert!(atmega328p.supports_interface(&ProgrammingInterface::DebugWire));
+        assert!(!atmega328p.supports_interface(&ProgrammingInterface::Updi));
+
+        let attiny816 = load_pack("tiny", "ATtiny816");
+        assert_eq!(vec![ProgrammingInterface::Updi], attiny816.device.interfaces);
+    }
 
-fn read_hex(value: Option<&String>) -> u32 {
-    let value = value.unwrap().replacen("0x", "", 1);
-    u32::from_str_radix(&value, 16).unwrap()
+    #[test]
+    fn attiny816_moded_registers_have_bitfields() {
+        let mcu = load_pack("tiny", "ATtiny816");
+        let intflags =
+            mcu.module("SPI").unwrap().registers().find(|r| r.name == "INTFLAGS").unwrap();
+
+        assert!(intflags.bitfields.is_empty());
+        assert_eq!(2, intflags.modes.len());
+
+        let default: Vec<_> = intflags.bitfields_for_mode("DEFAULT").map(|b| &b.name[..]).collect();
+        assert_eq!(vec!["IF", "WRCOL"], default);
+        assert_eq!(5, intflags.bitfields_for_mode("BUFFERED").count());
+        assert_eq!(0, intflags.bitfields_for_mode("NONEXISTENT").count());
+    }
+
+    #[test]
+    fn attiny816_tca_is_a_union() {
+        let mcu = load_pack("tiny", "ATtiny816");
+        let tca = mcu.module("TCA").unwrap();
+        let union = tca.register_group("TCA").unwrap();
+
+        assert!(union.is_union());
+        assert_eq!(Some(0x40), union.size);
+
+        let split = union.register_group_ref("SPLIT").unwrap();
+        assert_eq!(Some(1), split.union_tag_value);
+        assert_eq!("TCA_SPLIT", tca.resolve_register_group(split).unwrap().name);
+
+        let (register, bitfield) = tca.union_tag_bitfield(union).unwrap();
+        assert_eq!(("CTRLD", "SPLITM"), (&register.name[..], &bitfield.name[..]));
+    }
+
+    #[test]
+    fn instance_registers_have_absolute_addresses() {
+        let attiny816 = load_pack("tiny", "ATtiny816");
+        let porta = attiny816.instance("PORTA").unwrap();
+        let registers = attiny816.instance_registers(porta);
+        let out = registers.iter().find(|r| r.name == "OUT").unwrap();
+        assert_eq!(("data", 0x0404), (out.address_space, out.address));
+
+        let atmega328p = load_pack("atmega", "ATmega328P");
+        let usart0 = atmega328p.instance("USART0").unwrap();
+        let registers = atmega328p.instance_registers(usart0);
+        let udr0 = registers.iter().find(|r| r.name == "UDR0").unwrap();
+        assert_eq!(("data", 0xC6), (udr0.address_space, udr0.address));
+
+        let atxmega128a1 = load_pack("xmegaa", "ATxmega128A1");
+        let dma = atxmega128a1.instance("DMA").unwrap();
+        let registers = atxmega128a1.instance_registers(dma);
+        let ch1_ctrla = registers.iter().find(|r| r.name == "CH1.CTRLA").unwrap();
+        assert_eq!(0x0100 + 0x20, ch1_ctrla.address);
+    }
+
+    #[test]
+    fn signal_functions_are_read() {
+        let atmega328p = load_pack("atmega", "ATmega328P");
+        assert_eq!("Two Wire Serial Interface", atmega328p.instance("TWI").unwrap().caption);
+
+        let attiny816 = load_pack("tiny", "ATtiny816");
+        let usart = attiny816.peripheral("USART").unwrap();
+        let default_txd: Vec<_> = usart
+            .signals_for_function("USART0")
+            .filter(|s| s.group.as_ref().map(String::as_ref) == Some("TXD"))
+            .map(|s| &s.pad[..])
+            .collect();
+        let alternate_txd: Vec<_> = usart
+            .signals_for_function("USART0_ALT")
+            .filter(|s| s.group.as_ref().map(String::as_ref) == Some("TXD"))
+            .map(|s| &s.pad[..])
+            .collect();
+
+        assert_eq!(vec!["PB2"], default_txd);
+        assert_eq!(vec!["PA1"], alternate_txd);
+    }
+
+    #[test]
+    fn reset_state_is_computed() {
+        let atmega328p = load_pack("atmega", "ATmega328P");
+        let high = atmega328p.module("FUSE").unwrap().registers().find(|r| r.name == "HIGH");
+        assert_eq!(Some(0xD9), high.unwrap().initial_value);
+
+        let atmega4809 = load_pack("atmega", "ATmega4809");
+        let state = atmega4809.reset_state();
+        // AC0.DACREF
+        assert_eq!(Some(&0xFF), state.get(&(0x0680 + 0x4)));
+        // AC0.CTRLA
+        assert_eq!(Some(&0x00), state.get(&0x0680));
+    }
+
+    #[test]
+    fn memory_regions_are_normalized() {
+        let atmega328p = load_pack("atmega", "ATmega328P").memory();
+        assert_eq!(Some(0x7FFF), atmega328p.flashend());
+        assert_eq!(Some(0x80), atmega328p.spm_pagesize());
+        assert_eq!((Some(0x100), Some(0x8FF)), (atmega328p.ramstart(), atmega328p.ramend()));
+        assert_eq!(Some(0x3FF), atmega328p.e2end());
+        assert_eq!(Some(4), atmega328p.eeprom.unwrap().page_size);
+        assert_eq!(None, atmega328p.user_signature);
+
+        // AVR8X parts map the EEPROM, fuses and user signatures into data memory.
+        let attiny816 = load_pack("tiny", "ATtiny816").memory();
+        assert_eq!((Some(0x3E00), Some(0x3FFF)), (attiny816.ramstart(), attiny816.ramend()));
+        assert_eq!(Some(0x7F), attiny816.e2end());
+        let eeprom = attiny816.eeprom.unwrap();
+        assert_eq!(("data", 0x1400, 0x80), (&eeprom.address_space[..], eeprom.start, eeprom.size));
+        let fuses = attiny816.fuses.unwrap();
+        assert_eq!((0x1280, 0xA), (fuses.start, fuses.size));
+        assert_eq!(0x1300, attiny816.user_signature.unwrap().start);
+
+        // The external SRAM is not part of the internal SRAM.
+        let atxmega128a1u = load_pack("xmegaa", "ATxmega128A1U").memory();
+        assert_eq!(Some(0x21FFF), atxmega128a1u.flashend());
+        assert_eq!(Some(512), atxmega128a1u.spm_pagesize());
+        assert_eq!(
+            (Some(0x2000), Some(0x3FFF)),
+            (atxmega128a1u.ramstart(), atxmega128a1u.ramend())
+        );
+        assert_eq!("eeprom", atxmega128a1u.eeprom.unwrap().address_space);
+        assert_eq!(Some(512), atxmega128a1u.user_signature.unwrap().page_size);
+
+        // The ATtiny11 cannot write its own flash, and has no SRAM or EEPROM.
+        let attiny11 = load_pack("tiny", "ATtiny11").memory();
+        assert_eq!(Some(0x3FF), attiny11.flashend());
+        assert_eq!(None, attiny11.spm_pagesize());
+        assert_eq!((None, None), (attiny11.ramend(), attiny11.e2end()));
+
+        let eeprom =
+            MemoryRegion { address_space: "eeprom".to_owned(), start: 0, size: 0, page_size: None };
+        assert_eq!(None, eeprom.end());
+        assert_eq!(None, MemoryMap { eeprom: Some(eeprom), ..attiny11 }.e2end());
+    }
+
+    #[test]
+    fn access_is_read_from_rw_attributes() {
+        let attiny816 = load_pack("tiny", "ATtiny816");
+        let bod = attiny816.module("BOD").unwrap();
+        let status = bod.registers().find(|r| r.name == "STATUS").unwrap();
+        assert_eq!(ReadWrite::ReadAndWrite, status.rw);
+        assert_eq!(ReadWrite::ReadOnly, status.bitfields[0].access);
+        assert!(!status.bitfields[0].access.is_writable());
+
+        let adc = attiny816.module("ADC").unwrap();
+        let res = adc.registers().find(|r| r.name == "RES").unwrap();
+        assert_eq!(ReadWrite::ReadOnly, res.rw);
+
+        let atmega328p = load_pack("atmega", "ATmega328P");
+        let usart = atmega328p.module("USART").unwrap();
+        let udr0 = usart.registers().find(|r| r.name == "UDR0").unwrap();
+        assert_eq!(ReadWrite::ReadAndWrite, udr0.rw);
+        assert_eq!(ReadWrite::NoAccess, udr0.ocd_rw);
+    }
+
+    #[test]
+    fn parameters_are_read() {
+        let atmega328p = load_pack("atmega", "ATmega328P");
+        assert_eq!(Some(CoreVersion::V2E), atmega328p.core_version());
+
+        let atxmega128a1u = load_pack("xmegaa", "ATxmega128A1U");
+        assert_eq!(Some(CoreVersion::V3XJ), atxmega128a1u.core_version());
+        assert_eq!(
+            Some(&"V3XJ".to_owned()),
+            atxmega128a1u.module("CPU").unwrap().parameters.get("CORE_VERSION")
+        );
+
+        let atmega256rfr2 = load_pack("atmega", "ATmega256RFR2");
+        assert_eq!(
+            Some(&"1".to_owned()),
+            atmega256rfr2.device.parameters.get("SUPPORTS_EEPROM_ERASE")
+        );
+    }
 }
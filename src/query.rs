@@ -0,0 +1,330 @@
+//! Parametric searches for microcontrollers.
+
+use model::{Architecture, Mcu, MemoryRegion, ProgrammingInterface, Variant};
+
+use std::cmp::Ordering;
+
+/// A search for microcontroller variants matching a set of requirements.
+///
+/// Each variant of a microcontroller, such as a specific package or speed
+/// grade, is matched separately.
+///
+/// # Examples
+///
+/// ```nodoc
+/// use avr_mcu::{McuQuery, RankKey};
+///
+/// let matches = McuQuery::new()
+///     .min_flash(32 * 1024)
+///     .min_instances("USART", 2)
+///     .package("QFN")
+///     .voltage(1.8)
+///     .temperature(-40, 105)
+///     .rank_by(RankKey::FlashSize)
+///     .search();
+///
+/// for m in matches {
+///     println!("{} ({})", m.mcu.device.name, m.variant.name);
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct McuQuery {
+    min_flash: Option<u32>,
+    min_sram: Option<u32>,
+    min_eeprom: Option<u32>,
+    instance_counts: Vec<(String, usize)>,
+    interrupts: Vec<String>,
+    packages: Vec<String>,
+    voltage: Option<f32>,
+    temperature: Option<(i32, i32)>,
+    min_speed_hz: Option<u64>,
+    interfaces: Vec<ProgrammingInterface>,
+    architectures: Vec<Architecture>,
+    rank_key: RankKey,
+    descending: bool,
+}
+
+/// A property that search results can be ranked by.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum RankKey {
+    /// The name of the microcontroller, then the name of the variant.
+    #[default]
+    Name,
+    /// The size of the flash memory.
+    FlashSize,
+    /// The size of the internal SRAM.
+    SramSize,
+    /// The size of the EEPROM.
+    EepromSize,
+    /// The maximum clock speed of the variant.
+    SpeedMax,
+    /// The minimum supply voltage of the variant.
+    VoltageMin,
+    /// The number of pins of the variant's package.
+    PinCount,
+}
+
+/// A microcontroller variant matching a query.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct QueryMatch<'a> {
+    /// The matching microcontroller.
+    pub mcu: &'a Mcu,
+    /// The matching variant of the microcontroller.
+    pub variant: &'a Variant,
+}
+
+impl McuQuery {
+    /// Creates a query that matches every variant, ranked by name.
+    pub fn new() -> Self {
+        McuQuery::default()
+    }
+
+    /// Requires a minimum flash size in bytes.
+    pub fn min_flash(mut self, bytes: u32) -> Self {
+        self.min_flash = Some(bytes);
+        self
+    }
+
+    /// Requires a minimum internal SRAM size in bytes.
+    pub fn min_sram(mut self, bytes: u32) -> Self {
+        self.min_sram = Some(bytes);
+        self
+    }
+
+    /// Requires a minimum EEPROM size in bytes.
+    pub fn min_eeprom(mut self, bytes: u32) -> Self {
+        self.min_eeprom = Some(bytes);
+        self
+    }
+
+    /// Requires a minimum number of instances of a peripheral, such as two
+    /// instances of `USART`.
+    ///
+    /// The peripheral name is not case sensitive.
+    pub fn min_instances(mut self, peripheral: &str, count: usize) -> Self {
+        self.instance_counts.push((peripheral.to_owned(), count));
+        self
+    }
+
+    /// Requires an interrupt, such as `USART0_RX`.
+    ///
+    /// The interrupt name is not case sensitive.
+    pub fn interrupt(mut self, name: &str) -> Self {
+        self.interrupts.push(name.to_owned());
+        self
+    }
+
+    /// Requires the package name of the variant to contain a string, such as `QFN`.
+    ///
+    /// If this is given several times, any of the packages match. The package
+    /// name is not case sensitive.
+    pub fn package(mut self, package: &str) -> Self {
+        self.packages.push(package.to_lowercase());
+        self
+    }
+
+    /// Requires the variant to operate at a supply voltage.
+    pub fn voltage(mut self, volts: f32) -> Self {
+        self.voltage = Some(volts);
+        self
+    }
+
+    /// Requires the variant to be rated for a temperature range in °C.
+    pub fn temperature(mut self, min: i32, max: i32) -> Self {
+        self.temperature = Some((min, max));
+        self
+    }
+
+    /// Requires a minimum maximum clock speed of the variant.
+    pub fn min_speed_hz(mut self, hz: u64) -> Self {
+        self.min_speed_hz = Some(hz);
+        self
+    }
+
+    /// Requires support for a programming or debugging interface.
+    pub fn interface(mut self, interface: ProgrammingInterface) -> Self {
+        self.interfaces.push(interface);
+        self
+    }
+
+    /// Requires an avr-gcc architecture.
+    ///
+    /// If this is given several times, any of the architectures match.
+    pub fn architecture(mut self, architecture: Architecture) -> Self {
+        self.architectures.push(architecture);
+        self
+    }
+
+    /// Sets the property that results are ranked by, in ascending order.
+    pub fn rank_by(mut self, key: RankKey) -> Self {
+        self.rank_key = key;
+        self
+    }
+
+    /// Ranks results in descending order.
+    pub fn descending(mut self) -> Self {
+        self.descending = true;
+        self
+    }
+
+    /// Searches all microcontrollers.
+    pub fn search(&self) -> Vec<QueryMatch<'static>> {
+        self.search_in(::microcontrollers())
+    }
+
+    /// Searches a list of microcontrollers.
+    pub fn search_in<'a>(&self, mcus: &'a [Mcu]) -> Vec<QueryMatch<'a>> {
+        let matches: Vec<_> = mcus
+            .iter()
+            .filter(|mcu| self.matches_mcu(mcu))
+            .flat_map(|mcu| {
+                mcu.variants
+                    .iter()
+                    .filter(move |variant| self.matches_variant(variant))
+                    .map(move |variant| QueryMatch { mcu, variant })
+            })
+            .collect();
+
+        // Ranking needs the memory map, which is expensive to build, so
+        // compute the rank of each match once.
+        let mut ranked: Vec<_> = matches.into_iter().map(|m| (self.rank(&m), m)).collect();
+        ranked.sort_by(|(a_rank, a), (b_rank, b)| {
+            let ordering = a_rank.partial_cmp(b_rank).unwrap_or(Ordering::Equal).then_with(|| {
+                (&a.mcu.device.name, &a.variant.name).cmp(&(&b.mcu.device.name, &b.variant.name))
+            });
+            if self.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+        ranked.into_iter().map(|(_, m)| m).collect()
+    }
+
+    /// Checks the requirements that do not depend on the variant.
+    #[allow(clippy::unnecessary_map_or)]
+    fn matches_mcu(&self, mcu: &Mcu) -> bool {
+        let at_least = |min: Option<u32>, size: u32| min.map_or(true, |min| size >= min);
+        let memory = mcu.memory();
+
+        at_least(self.min_flash, size(memory.flash))
+            && at_least(self.min_sram, size(memory.sram))
+            && at_least(self.min_eeprom, size(memory.eeprom))
+            && self.instance_counts.iter().all(|(name, count)| instance_count(mcu, name) >= *count)
+            && self
+                .interrupts
+                .iter()
+                .all(|name| mcu.device.interrupts.iter().any(|i| i.name.eq_ignore_ascii_case(name)))
+            && self.interfaces.iter().all(|interface| mcu.supports_interface(interface))
+            && (self.architectures.is_empty() || self.architectures.contains(&mcu.architecture))
+    }
+
+    #[allow(clippy::unnecessary_map_or)]
+    fn matches_variant(&self, variant: &Variant) -> bool {
+        let package = variant.package.to_lowercase();
+
+        (self.packages.is_empty() || self.packages.iter().any(|p| package.contains(p.as_str())))
+            && self.voltage.map_or(true, |v| variant.voltage_min <= v && v <= variant.voltage_max)
+            && self.temperature.map_or(true, |(min, max)| {
+                variant.temperature_min <= min && max <= variant.temperature_max
+            })
+            && self.min_speed_hz.map_or(true, |hz| variant.speed_max_hz >= hz)
+    }
+
+    /// Gets the value of the rank key for a match.
+    ///
+    /// Matches with the same rank are ordered by name.
+    fn rank(&self, m: &QueryMatch) -> f64 {
+        match self.rank_key {
+            RankKey::Name => 0.0,
+            RankKey::FlashSize => f64::from(flash_size(m.mcu)),
+            RankKey::SramSize => f64::from(sram_size(m.mcu)),
+            RankKey::EepromSize => f64::from(eeprom_size(m.mcu)),
+            RankKey::SpeedMax => m.variant.speed_max_hz as f64,
+            RankKey::VoltageMin => f64::from(m.variant.voltage_min),
+            RankKey::PinCount => pin_count(m) as f64,
+        }
+    }
+}
+
+fn size(region: Option<MemoryRegion>) -> u32 {
+    region.map_or(0, |region| region.size)
+}
+
+fn flash_size(mcu: &Mcu) -> u32 {
+    size(mcu.memory().flash)
+}
+
+fn sram_size(mcu: &Mcu) -> u32 {
+    size(mcu.memory().sram)
+}
+
+fn eeprom_size(mcu: &Mcu) -> u32 {
+    size(mcu.memory().eeprom)
+}
+
+fn instance_count(mcu: &Mcu, peripheral: &str) -> usize {
+    mcu.device
+        .peripherals
+        .iter()
+        .filter(|p| p.name.eq_ignore_ascii_case(peripheral))
+        .map(|p| p.instances.len())
+        .sum()
+}
+
+fn pin_count(m: &QueryMatch) -> usize {
+    m.variant.pinout(m.mcu).map_or(0, |pinout| pinout.pins.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "family-atmega")]
+    fn variants_are_filtered() {
+        let matches = McuQuery::new()
+            .min_flash(32 * 1024)
+            .min_instances("usart", 2)
+            .package("QFN")
+            .voltage(1.8)
+            .temperature(-40, 105)
+            .search();
+
+        assert!(!matches.is_empty());
+        for m in matches.iter() {
+            assert!(flash_size(m.mcu) >= 32 * 1024);
+            assert!(instance_count(m.mcu, "USART") >= 2);
+            assert!(m.variant.package.contains("QFN"));
+            assert!(m.variant.voltage_min <= 1.8 && m.variant.temperature_max >= 105);
+        }
+        assert!(matches.iter().any(|m| m.mcu.device.name == "ATmega324PB"));
+        assert!(!matches.iter().any(|m| m.mcu.device.name == "ATmega328P"));
+    }
+
+    #[test]
+    #[cfg(all(feature = "family-atmega", feature = "family-tiny"))]
+    fn mcu_requirements_are_checked() {
+        let matches = McuQuery::new()
+            .interface(ProgrammingInterface::Updi)
+            .architecture(Architecture::Xmega3)
+            .search();
+        assert!(matches.iter().any(|m| m.mcu.device.name == "ATtiny816"));
+        assert!(matches.iter().all(|m| m.mcu.architecture == Architecture::Xmega3));
+
+        let matches = McuQuery::new().interrupt("usart1_rx").search();
+        assert!(matches.iter().any(|m| m.mcu.device.name == "ATmega324PB"));
+        assert!(!matches.iter().any(|m| m.mcu.device.name == "ATmega328P"));
+    }
+
+    #[test]
+    fn matches_are_ranked() {
+        let matches = McuQuery::new().package("PDIP").rank_by(RankKey::FlashSize).search();
+        let sizes: Vec<_> = matches.iter().map(|m| flash_size(m.mcu)).collect();
+        assert!(sizes.windows(2).all(|w| w[0] <= w[1]));
+
+        let matches = McuQuery::new().rank_by(RankKey::SpeedMax).descending().search();
+        let speeds: Vec<_> = matches.iter().map(|m| m.variant.speed_max_hz).collect();
+        assert!(speeds.windows(2).all(|w| w[0] >= w[1]));
+    }
+}